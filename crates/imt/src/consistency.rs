@@ -0,0 +1,463 @@
+use crate::hash::Hasher;
+use crate::imt::{capacity, IMTError, IMTNode, IMT};
+use crate::store::IMTStore;
+
+/// A proof that an [`IMT`] root of `new_size` leaves extends a root of `old_size` leaves, i.e.
+/// that the first `old_size` leaves are the same in both trees.
+///
+/// It follows the path of the last leaf of the old tree. At each level, the nodes on the left of
+/// the path only depend on old leaves, so they are shared by both trees. The nodes on the right
+/// are zeroes in the old tree, and they are included for the new tree only when they are not
+/// zeroes too, i.e. when their subtree contains some of the first `new_size` leaves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IMTConsistencyProof<N = IMTNode> {
+    depth: usize,
+    arity: usize,
+    leaf: Option<N>,
+    left_siblings: Vec<Vec<N>>,
+    right_siblings: Vec<Vec<N>>,
+}
+
+impl<N> IMTConsistencyProof<N> {
+    /// Creates a proof from its parts, checking that they match the depth and the arity.
+    ///
+    /// A proof without a leaf has no siblings. Otherwise, there must be one group of left and
+    /// one group of right siblings per level, with at most `arity - 1` siblings in both.
+    pub fn new(
+        depth: usize,
+        arity: usize,
+        leaf: Option<N>,
+        left_siblings: Vec<Vec<N>>,
+        right_siblings: Vec<Vec<N>>,
+    ) -> Result<IMTConsistencyProof<N>, IMTError> {
+        let proof = IMTConsistencyProof {
+            depth,
+            arity,
+            leaf,
+            left_siblings,
+            right_siblings,
+        };
+
+        proof.validate()?;
+
+        Ok(proof)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// The last leaf of the old tree, or `None` if the old tree is empty.
+    pub fn leaf(&self) -> Option<&N> {
+        self.leaf.as_ref()
+    }
+
+    pub fn left_siblings(&self) -> &[Vec<N>] {
+        &self.left_siblings
+    }
+
+    pub fn right_siblings(&self) -> &[Vec<N>] {
+        &self.right_siblings
+    }
+
+    fn validate(&self) -> Result<(), IMTError> {
+        if capacity(self.depth, self.arity).is_err() {
+            return Err(IMTError::MalformedProof(
+                "The depth and the arity of the proof are not supported",
+            ));
+        }
+
+        let levels = if self.leaf.is_some() { self.depth } else { 0 };
+
+        if self.left_siblings.len() != levels || self.right_siblings.len() != levels {
+            return Err(IMTError::MalformedProof(
+                "The proof must have siblings for each level if and only if it has a leaf",
+            ));
+        }
+
+        if self
+            .left_siblings
+            .iter()
+            .zip(&self.right_siblings)
+            .any(|(left, right)| left.len() + right.len() >= self.arity)
+        {
+            return Err(IMTError::MalformedProof(
+                "Each level of the proof must have at most arity - 1 siblings",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<H: Hasher, S: IMTStore<H::Node>> IMT<H, S> {
+    /// Creates a proof that the root of the first `new_size` leaves of the tree extends the root
+    /// of its first `old_size` leaves.
+    pub fn create_consistency_proof(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<IMTConsistencyProof<H::Node>, IMTError> {
+        if old_size > new_size || (new_size > 0 && self.check_leaf_index(new_size - 1).is_err()) {
+            return Err(IMTError::InvalidTreeSizes { old_size, new_size });
+        }
+
+        let mut proof = IMTConsistencyProof {
+            depth: self.depth(),
+            arity: self.arity(),
+            leaf: None,
+            left_siblings: vec![],
+            right_siblings: vec![],
+        };
+
+        if old_size == 0 {
+            return Ok(proof);
+        }
+
+        let mut index = old_size - 1;
+        let mut width = 1;
+
+        proof.leaf = Some(self.node_or_zero(0, index)?);
+
+        for level in 0..self.depth() {
+            let level_start_index = index - index % self.arity();
+            let level_end_index = level_start_index + self.arity();
+
+            // The subtrees on the left only contain old leaves.
+            proof.left_siblings.push(
+                (level_start_index..index)
+                    .map(|i| self.node_or_zero(level, i))
+                    .collect::<Result<_, _>>()?,
+            );
+            proof.right_siblings.push(
+                (index + 1..level_end_index)
+                    .take_while(|i| i * width < new_size)
+                    .map(|i| self.prefix_node(level, i, width, new_size))
+                    .collect::<Result<_, _>>()?,
+            );
+
+            index /= self.arity();
+            width *= self.arity();
+        }
+
+        Ok(proof)
+    }
+
+    /// Returns the node at the given position in the tree made of the first `size` leaves.
+    ///
+    /// `width` is the number of leaves under a node of the level, i.e. `arity^level`.
+    fn prefix_node(
+        &self,
+        level: usize,
+        index: usize,
+        width: usize,
+        size: usize,
+    ) -> Result<H::Node, IMTError> {
+        if (index + 1) * width <= size {
+            return self.node_or_zero(level, index);
+        }
+
+        if index * width >= size {
+            return Ok(self.zero(level).clone());
+        }
+
+        let child_width = width / self.arity();
+        let children = (index * self.arity()..(index + 1) * self.arity())
+            .map(|i| self.prefix_node(level - 1, i, child_width, size))
+            .collect::<Result<_, _>>()?;
+
+        Ok(self.hasher().hash(children))
+    }
+}
+
+impl<H: Hasher> IMT<H> {
+    /// Verifies that `new_root`, the root of a tree of `new_size` leaves, extends `old_root`,
+    /// the root of a tree of `old_size` leaves, padded with the zeroes derived from `zero_value`.
+    ///
+    /// As [`IMT::root`], the root of an empty tree is `None`.
+    ///
+    /// The old tree is fully determined by the proof, but the new one is only checked to be
+    /// zero after the right siblings of the path, so `new_size` is checked up to the size of
+    /// the last non-zero sibling.
+    pub fn verify_consistency_proof(
+        hash: &H,
+        zero_value: &H::Node,
+        old_size: usize,
+        old_root: Option<&H::Node>,
+        new_size: usize,
+        new_root: Option<&H::Node>,
+        proof: &IMTConsistencyProof<H::Node>,
+    ) -> bool {
+        let arity = proof.arity;
+
+        if proof.validate().is_err() {
+            return false;
+        }
+
+        match capacity(proof.depth, arity) {
+            Ok(capacity) if old_size <= new_size && new_size <= capacity => {},
+            _ => return false,
+        }
+
+        let Some(leaf) = &proof.leaf else {
+            // An empty tree is extended by any tree.
+            return old_size == 0 && old_root.is_none() && (new_size == 0) == new_root.is_none();
+        };
+
+        let (Some(old_root), Some(new_root)) = (old_root, new_root) else {
+            return false;
+        };
+
+        if old_size == 0 {
+            return false;
+        }
+
+        let mut zeroes = vec![zero_value.clone()];
+        for _ in 0..proof.depth {
+            let zero = zeroes[zeroes.len() - 1].clone();
+            zeroes.push(hash.hash(vec![zero; arity]));
+        }
+
+        let mut old_node = leaf.clone();
+        let mut new_node = leaf.clone();
+        let mut index = old_size - 1;
+        let mut width = 1;
+
+        let levels = zeroes
+            .iter()
+            .zip(&proof.left_siblings)
+            .zip(&proof.right_siblings);
+
+        for ((zero, left_siblings), right_siblings) in levels {
+            let position = index % arity;
+            let filled_right_siblings = (index + 1..index - position + arity)
+                .take_while(|i| i * width < new_size)
+                .count();
+
+            if left_siblings.len() != position || right_siblings.len() != filled_right_siblings {
+                return false;
+            }
+
+            let zero_padding = |filled: usize| vec![zero.clone(); arity - 1 - position - filled];
+
+            let mut children = left_siblings.clone();
+            children.push(old_node);
+            children.extend(zero_padding(0));
+            old_node = hash.hash(children);
+
+            let mut children = left_siblings.clone();
+            children.push(new_node);
+            children.extend(right_siblings.iter().cloned());
+            children.extend(zero_padding(filled_right_siblings));
+            new_node = hash.hash(children);
+
+            index /= arity;
+            width *= arity;
+        }
+
+        old_node == *old_root && new_node == *new_root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imt::IMTHashFunction;
+
+    fn hash_function(nodes: Vec<String>) -> String {
+        format!("H({})", nodes.join(","))
+    }
+
+    fn tree(arity: usize, leaves: &[String]) -> IMT {
+        let hash: IMTHashFunction = hash_function;
+        IMT::new(hash, 3, "0".to_string(), arity, leaves.to_vec()).unwrap()
+    }
+
+    fn root(arity: usize, leaves: &[String]) -> Option<String> {
+        tree(arity, leaves).root()
+    }
+
+    fn verify(
+        old: (usize, Option<&String>),
+        new: (usize, Option<&String>),
+        proof: &IMTConsistencyProof,
+    ) -> bool {
+        let hash: IMTHashFunction = hash_function;
+        IMT::verify_consistency_proof(&hash, &"0".to_string(), old.0, old.1, new.0, new.1, proof)
+    }
+
+    #[test]
+    fn test_create_and_verify_consistency_proof() {
+        for arity in 2..=3 {
+            let leaves: Vec<_> = (0..arity * arity + 2).map(|i| format!("leaf{i}")).collect();
+            let imt = tree(arity, &leaves);
+
+            for new_size in 0..=leaves.len() {
+                let new_root = root(arity, &leaves[..new_size]);
+
+                for old_size in 0..=new_size {
+                    let old_root = root(arity, &leaves[..old_size]);
+                    let proof = imt.create_consistency_proof(old_size, new_size).unwrap();
+
+                    assert!(verify(
+                        (old_size, old_root.as_ref()),
+                        (new_size, new_root.as_ref()),
+                        &proof
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn should_not_verify_modified_old_leaves() {
+        let leaves: Vec<_> = (0..6).map(|i| format!("leaf{i}")).collect();
+        let old_root = root(2, &leaves[..3]);
+
+        let mut modified = leaves.clone();
+        modified[1] = "changed".to_string();
+        let imt = tree(2, &modified);
+        let proof = imt.create_consistency_proof(3, 6).unwrap();
+        let new_root = root(2, &modified);
+
+        assert!(!verify(
+            (3, old_root.as_ref()),
+            (6, new_root.as_ref()),
+            &proof
+        ));
+    }
+
+    #[test]
+    fn should_not_verify_wrong_sizes() {
+        let leaves: Vec<_> = (0..6).map(|i| format!("leaf{i}")).collect();
+        let imt = tree(2, &leaves);
+        let proof = imt.create_consistency_proof(3, 5).unwrap();
+        let old_root = root(2, &leaves[..3]);
+        let new_root = root(2, &leaves[..5]);
+
+        assert!(verify(
+            (3, old_root.as_ref()),
+            (5, new_root.as_ref()),
+            &proof
+        ));
+        assert!(!verify(
+            (2, old_root.as_ref()),
+            (5, new_root.as_ref()),
+            &proof
+        ));
+        assert!(!verify(
+            (3, old_root.as_ref()),
+            (4, new_root.as_ref()),
+            &proof
+        ));
+        assert!(!verify(
+            (5, new_root.as_ref()),
+            (3, old_root.as_ref()),
+            &proof
+        ));
+    }
+
+    #[test]
+    fn test_empty_tree_has_no_root() {
+        let leaves: Vec<_> = (0..3).map(|i| format!("leaf{i}")).collect();
+        let imt = tree(2, &leaves);
+        let empty = tree(2, &[]);
+        let new_root = imt.root();
+
+        let proof = imt.create_consistency_proof(0, 3).unwrap();
+        assert!(verify(
+            (0, empty.root().as_ref()),
+            (3, new_root.as_ref()),
+            &proof
+        ));
+        assert!(!verify(
+            (0, new_root.as_ref()),
+            (3, new_root.as_ref()),
+            &proof
+        ));
+        assert!(!verify((0, None), (3, None), &proof));
+
+        let proof = imt.create_consistency_proof(0, 0).unwrap();
+        assert!(verify((0, None), (0, None), &proof));
+
+        let proof = imt.create_consistency_proof(2, 3).unwrap();
+        assert!(!verify((2, None), (3, new_root.as_ref()), &proof));
+    }
+
+    #[test]
+    fn should_not_create_invalid_consistency_proof() {
+        let leaves: Vec<_> = (0..4).map(|i| format!("leaf{i}")).collect();
+        let imt = tree(2, &leaves);
+
+        assert_eq!(
+            imt.create_consistency_proof(3, 2),
+            Err(IMTError::InvalidTreeSizes {
+                old_size: 3,
+                new_size: 2
+            })
+        );
+        assert_eq!(
+            imt.create_consistency_proof(2, 5),
+            Err(IMTError::InvalidTreeSizes {
+                old_size: 2,
+                new_size: 5
+            })
+        );
+    }
+
+    #[test]
+    fn should_not_create_malformed_consistency_proof() {
+        let leaves: Vec<_> = (0..5).map(|i| format!("leaf{i}")).collect();
+        let proof = tree(2, &leaves).create_consistency_proof(3, 5).unwrap();
+        let parts = |proof: &IMTConsistencyProof| {
+            (
+                proof.leaf().cloned(),
+                proof.left_siblings().to_vec(),
+                proof.right_siblings().to_vec(),
+            )
+        };
+
+        let (leaf, left_siblings, right_siblings) = parts(&proof);
+        assert!(IMTConsistencyProof::new(
+            3,
+            2,
+            leaf.clone(),
+            left_siblings.clone(),
+            right_siblings.clone()
+        )
+        .is_ok());
+        assert!(IMTConsistencyProof::new(
+            3,
+            1,
+            leaf.clone(),
+            left_siblings.clone(),
+            right_siblings.clone()
+        )
+        .is_err());
+        assert!(IMTConsistencyProof::new(
+            2,
+            2,
+            leaf.clone(),
+            left_siblings.clone(),
+            right_siblings.clone()
+        )
+        .is_err());
+        assert!(IMTConsistencyProof::new(
+            3,
+            2,
+            None,
+            left_siblings.clone(),
+            right_siblings.clone()
+        )
+        .is_err());
+
+        // Two siblings and the node of the path do not fit in a binary group.
+        let mut too_many = right_siblings;
+        too_many[0].push("leaf4".to_string());
+        assert!(IMTConsistencyProof::new(3, 2, leaf, left_siblings, too_many).is_err());
+    }
+}
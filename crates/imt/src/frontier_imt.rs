@@ -0,0 +1,517 @@
+use crate::hash::Hasher;
+use crate::imt::{capacity, check_hasher_arity, IMTError, IMTHashFunction};
+
+/// Append-only Incremental Merkle Tree that only keeps the frontier of the tree.
+///
+/// Instead of every node, it stores the rightmost group of `arity` nodes of each level, as the
+/// `lastSubtrees` of zk-kit's and Semaphore's incremental tree contracts do. Memory usage is
+/// `O(depth * arity)`, and the tree supports insertions and the current root, but not updates
+/// or proofs.
+pub struct FrontierIMT<H: Hasher = IMTHashFunction> {
+    filled_subtrees: Vec<Vec<H::Node>>,
+    zeroes: Vec<H::Node>,
+    root: H::Node,
+    number_of_leaves: usize,
+    hash: H,
+    depth: usize,
+    arity: usize,
+}
+
+impl<H: Hasher> FrontierIMT<H> {
+    pub fn new(
+        hash: H,
+        depth: usize,
+        zero_value: H::Node,
+        arity: usize,
+    ) -> Result<FrontierIMT<H>, IMTError> {
+        if depth == 0 {
+            return Err(IMTError::InvalidDepth);
+        }
+
+        capacity(depth, arity)?;
+        check_hasher_arity(&hash, arity)?;
+
+        let mut zeroes = Vec::with_capacity(depth);
+        let mut current_zero = zero_value;
+        for _ in 0..depth {
+            zeroes.push(current_zero.clone());
+            current_zero = hash.hash(vec![current_zero; arity]);
+        }
+
+        let filled_subtrees = zeroes
+            .iter()
+            .map(|zero| vec![zero.clone(); arity])
+            .collect();
+
+        Ok(FrontierIMT {
+            filled_subtrees,
+            zeroes,
+            root: current_zero,
+            number_of_leaves: 0,
+            hash,
+            depth,
+            arity,
+        })
+    }
+
+    /// Rebuilds a tree from the filled subtrees and the number of leaves stored by an
+    /// incremental tree contract.
+    ///
+    /// `filled_subtrees[level]` must contain the `arity` nodes of the rightmost group of that
+    /// level, as zk-kit's `lastSubtrees`. The root is recomputed from the last group, and the
+    /// groups are checked against each other and against the number of leaves.
+    pub fn from_filled_subtrees(
+        hash: H,
+        depth: usize,
+        zero_value: H::Node,
+        arity: usize,
+        filled_subtrees: Vec<Vec<H::Node>>,
+        number_of_leaves: usize,
+    ) -> Result<FrontierIMT<H>, IMTError> {
+        let mut tree = FrontierIMT::new(hash, depth, zero_value, arity)?;
+
+        if filled_subtrees.len() != depth
+            || filled_subtrees.iter().any(|subtree| subtree.len() != arity)
+        {
+            return Err(IMTError::InvalidFilledSubtrees);
+        }
+
+        if number_of_leaves > tree.capacity() {
+            return Err(IMTError::TooManyLeaves);
+        }
+
+        if number_of_leaves == 0 {
+            if filled_subtrees != tree.filled_subtrees {
+                return Err(IMTError::InvalidFilledSubtrees);
+            }
+
+            return Ok(tree);
+        }
+
+        // The last group of each level contains the ancestor of the last leaf, followed by
+        // zeroes, and its hash is that ancestor on the next level.
+        let mut index = number_of_leaves - 1;
+        let mut node = None;
+
+        for (level, subtree) in filled_subtrees.iter().enumerate() {
+            let position = index % arity;
+
+            if subtree[position + 1..]
+                .iter()
+                .any(|right| *right != tree.zeroes[level])
+                || node.as_ref().is_some_and(|node| *node != subtree[position])
+            {
+                return Err(IMTError::InvalidFilledSubtrees);
+            }
+
+            node = Some(tree.hash.hash(subtree.clone()));
+            index /= arity;
+        }
+
+        tree.root = node.expect("The depth is not 0");
+        tree.filled_subtrees = filled_subtrees;
+        tree.number_of_leaves = number_of_leaves;
+
+        Ok(tree)
+    }
+
+    /// Rebuilds a binary tree from the filled subtrees and the number of leaves of Tornado
+    /// Cash's or Semaphore's `MerkleTreeWithHistory`-style contracts.
+    ///
+    /// `filled_subtrees[level]` must contain a single node: the last node of that level on
+    /// the left of its parent, i.e. the contracts' `filledSubtrees`. The root is recomputed and
+    /// the nodes are checked against the number of leaves where the contracts allow it.
+    ///
+    /// The contracts do not store the last node of a level when it is on the right of its
+    /// parent. It is only needed for the root of a full tree, which cannot be rebuilt, and is
+    /// left as a zero in the groups returned by [`FrontierIMT::filled_subtrees`], since
+    /// insertions never read it.
+    pub fn from_binary_filled_subtrees(
+        hash: H,
+        depth: usize,
+        zero_value: H::Node,
+        filled_subtrees: Vec<H::Node>,
+        number_of_leaves: usize,
+    ) -> Result<FrontierIMT<H>, IMTError> {
+        let mut tree = FrontierIMT::new(hash, depth, zero_value, 2)?;
+
+        if filled_subtrees.len() != depth {
+            return Err(IMTError::InvalidFilledSubtrees);
+        }
+
+        if number_of_leaves > tree.capacity() {
+            return Err(IMTError::TooManyLeaves);
+        }
+
+        if number_of_leaves == tree.capacity() {
+            return Err(IMTError::TreeFull {
+                capacity: tree.capacity(),
+            });
+        }
+
+        if number_of_leaves == 0 {
+            if filled_subtrees != tree.zeroes {
+                return Err(IMTError::InvalidFilledSubtrees);
+            }
+
+            return Ok(tree);
+        }
+
+        // Below the first level where the ancestor of the last leaf is a left node, the
+        // ancestors are right nodes, which the contracts do not store. From that level, the
+        // ancestors can be recomputed, and the left ones must be the stored nodes.
+        let last_index = number_of_leaves - 1;
+        let first_left_level = last_index.trailing_ones() as usize;
+        let mut node = filled_subtrees[first_left_level].clone();
+
+        for (level, left) in filled_subtrees.into_iter().enumerate() {
+            let zero = tree.zeroes[level].clone();
+
+            if level < first_left_level {
+                tree.filled_subtrees[level] = vec![left, zero];
+                continue;
+            }
+
+            let group = if (last_index >> level) & 1 == 0 {
+                if node != left {
+                    return Err(IMTError::InvalidFilledSubtrees);
+                }
+
+                vec![left, zero]
+            } else {
+                vec![left, node]
+            };
+
+            node = tree.hash.hash(group.clone());
+            tree.filled_subtrees[level] = group;
+        }
+
+        tree.root = node;
+        tree.number_of_leaves = number_of_leaves;
+
+        Ok(tree)
+    }
+
+    pub fn root(&self) -> H::Node {
+        self.root.clone()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Returns the maximum number of leaves of the tree, i.e. `arity^depth`.
+    pub fn capacity(&self) -> usize {
+        self.arity.pow(self.depth as u32)
+    }
+
+    pub fn number_of_leaves(&self) -> usize {
+        self.number_of_leaves
+    }
+
+    pub fn zeroes(&self) -> Vec<H::Node> {
+        self.zeroes.clone()
+    }
+
+    pub fn filled_subtrees(&self) -> Vec<Vec<H::Node>> {
+        self.filled_subtrees.clone()
+    }
+
+    pub fn insert(&mut self, leaf: H::Node) -> Result<(), IMTError> {
+        if self.number_of_leaves >= self.capacity() {
+            return Err(IMTError::TreeFull {
+                capacity: self.capacity(),
+            });
+        }
+
+        let mut index = self.number_of_leaves;
+        let mut node = leaf;
+
+        for level in 0..self.depth {
+            let position = index % self.arity;
+
+            // A new group starts at this level, so the nodes on the right are zeroes again.
+            if position == 0 {
+                self.filled_subtrees[level] = vec![self.zeroes[level].clone(); self.arity];
+            }
+
+            self.filled_subtrees[level][position] = node;
+            node = self.hash.hash(self.filled_subtrees[level].clone());
+            index /= self.arity;
+        }
+
+        self.root = node;
+        self.number_of_leaves += 1;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imt::IMT;
+
+    fn hash_function(nodes: Vec<String>) -> String {
+        format!("H({})", nodes.join(","))
+    }
+
+    #[test]
+    fn test_new_frontier_imt() {
+        let hash: IMTHashFunction = hash_function;
+        let tree = FrontierIMT::new(hash, 2, "0".to_string(), 2).unwrap();
+
+        assert_eq!(tree.root(), "H(H(0,0),H(0,0))");
+        assert_eq!(tree.number_of_leaves(), 0);
+        assert_eq!(
+            FrontierIMT::new(hash, 0, "0".to_string(), 2).err(),
+            Some(IMTError::InvalidDepth)
+        );
+        assert_eq!(
+            FrontierIMT::new(hash, 2, "0".to_string(), 1).err(),
+            Some(IMTError::InvalidArity)
+        );
+    }
+
+    #[test]
+    fn test_root_matches_imt() {
+        let hash: IMTHashFunction = hash_function;
+
+        for arity in 2..=4 {
+            let mut tree = FrontierIMT::new(hash, 3, "0".to_string(), arity).unwrap();
+            let mut imt = IMT::new(hash, 3, "0".to_string(), arity, vec![]).unwrap();
+
+            for i in 0..arity.pow(3) {
+                tree.insert(format!("leaf{i}")).unwrap();
+                imt.insert(format!("leaf{i}")).unwrap();
+
+                assert_eq!(Some(tree.root()), imt.root());
+            }
+        }
+    }
+
+    #[test]
+    fn should_not_insert_in_full_tree() {
+        let hash: IMTHashFunction = hash_function;
+        let mut tree = FrontierIMT::new(hash, 1, "0".to_string(), 2).unwrap();
+
+        tree.insert("leaf1".to_string()).unwrap();
+        tree.insert("leaf2".to_string()).unwrap();
+
+        assert_eq!(
+            tree.insert("leaf3".to_string()),
+            Err(IMTError::TreeFull { capacity: 2 })
+        );
+    }
+
+    #[test]
+    fn test_from_filled_subtrees() {
+        let hash: IMTHashFunction = hash_function;
+        let mut tree = FrontierIMT::new(hash, 3, "0".to_string(), 2).unwrap();
+
+        for i in 0..5 {
+            tree.insert(format!("leaf{i}")).unwrap();
+        }
+
+        let mut rebuilt = FrontierIMT::from_filled_subtrees(
+            hash,
+            3,
+            "0".to_string(),
+            2,
+            tree.filled_subtrees(),
+            tree.number_of_leaves(),
+        )
+        .unwrap();
+        assert_eq!(rebuilt.root(), tree.root());
+
+        tree.insert("leaf5".to_string()).unwrap();
+        rebuilt.insert("leaf5".to_string()).unwrap();
+        assert_eq!(rebuilt.root(), tree.root());
+        assert_eq!(rebuilt.number_of_leaves(), 6);
+
+        let empty = FrontierIMT::from_filled_subtrees(
+            hash,
+            3,
+            "0".to_string(),
+            2,
+            FrontierIMT::new(hash, 3, "0".to_string(), 2)
+                .unwrap()
+                .filled_subtrees(),
+            0,
+        )
+        .unwrap();
+        assert_eq!(empty.root(), "H(H(H(0,0),H(0,0)),H(H(0,0),H(0,0)))");
+    }
+
+    #[test]
+    fn should_not_rebuild_from_invalid_filled_subtrees() {
+        let hash: IMTHashFunction = hash_function;
+        let subtrees = vec![vec!["0".to_string(); 2]; 2];
+
+        assert!(FrontierIMT::from_filled_subtrees(
+            hash,
+            3,
+            "0".to_string(),
+            2,
+            subtrees.clone(),
+            1
+        )
+        .is_err());
+        assert!(FrontierIMT::from_filled_subtrees(
+            hash,
+            2,
+            "0".to_string(),
+            3,
+            subtrees.clone(),
+            1
+        )
+        .is_err());
+        assert!(
+            FrontierIMT::from_filled_subtrees(hash, 2, "0".to_string(), 2, subtrees, 5).is_err()
+        );
+    }
+
+    #[test]
+    fn should_not_rebuild_from_inconsistent_filled_subtrees() {
+        let hash: IMTHashFunction = hash_function;
+        let mut tree = FrontierIMT::new(hash, 3, "0".to_string(), 2).unwrap();
+
+        for i in 0..5 {
+            tree.insert(format!("leaf{i}")).unwrap();
+        }
+
+        let rebuild = |filled_subtrees, number_of_leaves| {
+            FrontierIMT::from_filled_subtrees(
+                hash,
+                3,
+                "0".to_string(),
+                2,
+                filled_subtrees,
+                number_of_leaves,
+            )
+            .err()
+        };
+
+        // With 6 leaves, the subtrees would only differ by a zero leaf, which is a valid leaf.
+        for number_of_leaves in [0, 4, 7] {
+            assert_eq!(
+                rebuild(tree.filled_subtrees(), number_of_leaves),
+                Some(IMTError::InvalidFilledSubtrees)
+            );
+        }
+
+        let mut forged = tree.filled_subtrees();
+        forged[1][0] = "forged".to_string();
+        assert_eq!(rebuild(forged, 5), Some(IMTError::InvalidFilledSubtrees));
+    }
+
+    /// Inserts leaves as Tornado Cash's `MerkleTreeWithHistory` does, and returns its
+    /// `filledSubtrees` and its last root.
+    fn contract_filled_subtrees(depth: usize, number_of_leaves: usize) -> (Vec<String>, String) {
+        let hash: IMTHashFunction = hash_function;
+        let zeroes = FrontierIMT::new(hash, depth, "0".to_string(), 2)
+            .unwrap()
+            .zeroes();
+        let mut filled_subtrees = zeroes.clone();
+        let mut root = String::new();
+
+        for leaf in 0..number_of_leaves {
+            let mut index = leaf;
+            let mut node = format!("leaf{leaf}");
+
+            for level in 0..depth {
+                node = if index % 2 == 0 {
+                    filled_subtrees[level] = node.clone();
+                    hash_function(vec![node, zeroes[level].clone()])
+                } else {
+                    hash_function(vec![filled_subtrees[level].clone(), node])
+                };
+                index /= 2;
+            }
+
+            root = node;
+        }
+
+        (filled_subtrees, root)
+    }
+
+    #[test]
+    fn test_from_binary_filled_subtrees() {
+        let hash: IMTHashFunction = hash_function;
+
+        for number_of_leaves in 0..8 {
+            let (filled_subtrees, root) = contract_filled_subtrees(3, number_of_leaves);
+            let mut tree = FrontierIMT::from_binary_filled_subtrees(
+                hash,
+                3,
+                "0".to_string(),
+                filled_subtrees,
+                number_of_leaves,
+            )
+            .unwrap();
+            let mut imt = IMT::new(
+                hash,
+                3,
+                "0".to_string(),
+                2,
+                (0..number_of_leaves).map(|i| format!("leaf{i}")).collect(),
+            )
+            .unwrap();
+
+            if number_of_leaves > 0 {
+                assert_eq!(tree.root(), root);
+                assert_eq!(Some(tree.root()), imt.root());
+            }
+
+            for i in number_of_leaves..8 {
+                tree.insert(format!("leaf{i}")).unwrap();
+                imt.insert(format!("leaf{i}")).unwrap();
+
+                assert_eq!(Some(tree.root()), imt.root());
+            }
+        }
+    }
+
+    #[test]
+    fn should_not_rebuild_from_inconsistent_binary_filled_subtrees() {
+        let hash: IMTHashFunction = hash_function;
+        let rebuild = |filled_subtrees, number_of_leaves| {
+            FrontierIMT::from_binary_filled_subtrees(
+                hash,
+                3,
+                "0".to_string(),
+                filled_subtrees,
+                number_of_leaves,
+            )
+            .err()
+        };
+
+        let (filled_subtrees, _) = contract_filled_subtrees(3, 5);
+        assert_eq!(
+            rebuild(filled_subtrees.clone(), 0),
+            Some(IMTError::InvalidFilledSubtrees)
+        );
+        assert_eq!(
+            rebuild(filled_subtrees.clone(), 3),
+            Some(IMTError::InvalidFilledSubtrees)
+        );
+        assert_eq!(
+            rebuild(filled_subtrees[..2].to_vec(), 5),
+            Some(IMTError::InvalidFilledSubtrees)
+        );
+        assert_eq!(
+            rebuild(filled_subtrees.clone(), 9),
+            Some(IMTError::TooManyLeaves)
+        );
+
+        let (filled_subtrees, _) = contract_filled_subtrees(3, 8);
+        assert_eq!(
+            rebuild(filled_subtrees, 8),
+            Some(IMTError::TreeFull { capacity: 8 })
+        );
+    }
+}
@@ -1,4 +1,50 @@
-use tiny_keccak::{Hasher, Keccak};
+use std::hash::Hash;
+
+use ark_bn254::Fr;
+use ark_crypto_primitives::sponge::poseidon::find_poseidon_ark_and_mds;
+use ark_ff::{Field, PrimeField, Zero};
+use tiny_keccak::{Hasher as _, Keccak};
+
+use crate::imt::{IMTError, IMTNode};
+
+/// Number of full rounds used by circomlib's Poseidon, for every width.
+const POSEIDON_FULL_ROUNDS: usize = 8;
+
+/// Number of partial rounds used by circomlib's Poseidon, indexed by `number of inputs - 1`.
+const POSEIDON_PARTIAL_ROUNDS: [usize; 16] = [
+    56, 57, 56, 60, 60, 63, 64, 63, 60, 66, 60, 65, 70, 60, 64, 68,
+];
+
+/// A hash function used to compute the parent of a group of child nodes.
+///
+/// The node type is an associated type, so trees can store field elements or fixed-size
+/// byte arrays directly. Nodes are hashable so that trees can index their leaves.
+/// Implementors can carry state, such as precomputed hash parameters.
+pub trait Hasher {
+    type Node: Clone + Eq + Hash;
+
+    /// Hashes the given child nodes into their parent node.
+    fn hash(&self, nodes: Vec<Self::Node>) -> Self::Node;
+
+    /// Returns the number of child nodes the hasher accepts, if it only accepts one, so that
+    /// trees of another arity can be rejected when they are created.
+    fn arity(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Any function over string nodes can be used as a hasher, which keeps the original
+/// string-based API working.
+impl<F> Hasher for F
+where
+    F: Fn(Vec<IMTNode>) -> IMTNode,
+{
+    type Node = IMTNode;
+
+    fn hash(&self, nodes: Vec<IMTNode>) -> IMTNode {
+        self(nodes)
+    }
+}
 
 pub fn keccak256_hash_function(nodes: Vec<String>) -> String {
     let mut keccak = Keccak::v256();
@@ -12,3 +58,273 @@ pub fn keccak256_hash_function(nodes: Vec<String>) -> String {
 
     hex::encode(result)
 }
+
+/// Keccak-256 over 32-byte words, equivalent to Solidity's
+/// `keccak256(abi.encodePacked(bytes32[]))`.
+///
+/// Roots computed with this hasher can be checked against on-chain incremental tree contracts
+/// that hash their nodes with `keccak256(abi.encodePacked(left, right))`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Keccak256Hasher;
+
+impl Hasher for Keccak256Hasher {
+    type Node = [u8; 32];
+
+    fn hash(&self, nodes: Vec<[u8; 32]>) -> [u8; 32] {
+        let mut keccak = Keccak::v256();
+        let mut result = [0u8; 32];
+
+        for node in nodes {
+            keccak.update(&node);
+        }
+
+        keccak.finalize(&mut result);
+
+        result
+    }
+}
+
+/// Hashes hex-encoded nodes as `keccak256(abi.encodePacked(bytes32[]))` and returns the
+/// hex-encoded result.
+///
+/// Each node is decoded as a big-endian 32-byte word: an optional `0x` prefix is accepted and
+/// shorter values are left-padded with zeros, as `bytes32(uint256(x))` would be.
+///
+/// # Panics
+///
+/// Panics if a node is not valid hexadecimal or is longer than 32 bytes.
+pub fn keccak256_packed_hash_function(nodes: Vec<String>) -> String {
+    let words = nodes.iter().map(|node| hex_to_word(node)).collect();
+
+    hex::encode(Keccak256Hasher.hash(words))
+}
+
+fn hex_to_word(node: &str) -> [u8; 32] {
+    let digits = node.strip_prefix("0x").unwrap_or(node);
+    assert!(
+        digits.len() <= 64,
+        "The node {node} does not fit in a 32-byte word"
+    );
+
+    let padded = format!("{digits:0>64}");
+    let mut word = [0u8; 32];
+    hex::decode_to_slice(padded, &mut word)
+        .unwrap_or_else(|_| panic!("The node {node} is not a valid hexadecimal string"));
+
+    word
+}
+
+/// Poseidon hash over the BN254 scalar field, compatible with circomlib's `Poseidon(nInputs)`
+/// template and circomlibjs' `poseidon` function.
+///
+/// The round constants and the MDS matrix are derived with the Grain LFSR of the Poseidon
+/// reference implementation, which is how circomlib generated its own constants. A hasher is
+/// bound to the number of inputs it was created for, which must be the arity of the tree.
+#[derive(Clone, Debug)]
+pub struct PoseidonHasher {
+    arity: usize,
+    partial_rounds: usize,
+    ark: Vec<Vec<Fr>>,
+    mds: Vec<Vec<Fr>>,
+}
+
+impl PoseidonHasher {
+    /// Creates a Poseidon hasher for `arity` inputs, with `2 <= arity <= 16`.
+    pub fn new(arity: usize) -> Result<PoseidonHasher, IMTError> {
+        if !(2..=POSEIDON_PARTIAL_ROUNDS.len()).contains(&arity) {
+            return Err(IMTError::InvalidArity);
+        }
+
+        let partial_rounds = POSEIDON_PARTIAL_ROUNDS[arity - 1];
+        let (ark, mds) = find_poseidon_ark_and_mds::<Fr>(
+            Fr::MODULUS_BIT_SIZE as u64,
+            arity,
+            POSEIDON_FULL_ROUNDS as u64,
+            partial_rounds as u64,
+            0,
+        );
+
+        Ok(PoseidonHasher {
+            arity,
+            partial_rounds,
+            ark,
+            mds,
+        })
+    }
+
+    /// Applies the Poseidon permutation to `[0, inputs...]` and returns the first element
+    /// of the resulting state.
+    fn permute(&self, inputs: &[Fr]) -> Fr {
+        let half_full_rounds = POSEIDON_FULL_ROUNDS / 2;
+        let mut state = Vec::with_capacity(self.arity + 1);
+        state.push(Fr::zero());
+        state.extend_from_slice(inputs);
+
+        for (round, constants) in self.ark.iter().enumerate() {
+            for (element, constant) in state.iter_mut().zip(constants) {
+                *element += constant;
+            }
+
+            if round < half_full_rounds || round >= half_full_rounds + self.partial_rounds {
+                for element in state.iter_mut() {
+                    *element = element.pow([5]);
+                }
+            } else {
+                state[0] = state[0].pow([5]);
+            }
+
+            state = self
+                .mds
+                .iter()
+                .map(|row| row.iter().zip(&state).map(|(m, s)| *m * s).sum())
+                .collect();
+        }
+
+        state[0]
+    }
+}
+
+impl Hasher for PoseidonHasher {
+    type Node = Fr;
+
+    fn hash(&self, nodes: Vec<Fr>) -> Fr {
+        assert_eq!(
+            nodes.len(),
+            self.arity,
+            "The number of nodes must match the Poseidon arity"
+        );
+
+        self.permute(&nodes)
+    }
+
+    fn arity(&self) -> Option<usize> {
+        Some(self.arity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontier_imt::FrontierIMT;
+    use crate::imt::IMT;
+    use std::str::FromStr;
+
+    fn fr(value: &str) -> Fr {
+        Fr::from_str(value).unwrap()
+    }
+
+    fn poseidon(inputs: &[u64]) -> Fr {
+        let hasher = PoseidonHasher::new(inputs.len()).unwrap();
+        hasher.hash(inputs.iter().map(|&i| Fr::from(i)).collect())
+    }
+
+    #[test]
+    fn test_poseidon_circomlibjs_vectors() {
+        assert_eq!(
+            poseidon(&[1, 2]),
+            fr("7853200120776062878684798364095072458815029376092732009249414926327459813530")
+        );
+        assert_eq!(
+            poseidon(&[1, 2, 3, 4]),
+            fr("18821383157269793795438455681495246036402687001665670618754263018637548127333")
+        );
+        assert_eq!(
+            poseidon(&[1, 2, 0, 0, 0]),
+            fr("1018317224307729531995786483840663576608797660851238720571059489595066344487")
+        );
+        assert_eq!(
+            poseidon(&[3, 4, 5, 10, 23]),
+            fr("13034429309846638789535561449942021891039729847501137143363028890275222221409")
+        );
+        assert_eq!(
+            poseidon(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]),
+            fr("9989051620750914585850546081941653841776809718687451684622678807385399211877")
+        );
+    }
+
+    #[test]
+    fn test_poseidon_imt_zeroes() {
+        let hasher = PoseidonHasher::new(2).unwrap();
+        let imt = IMT::new(hasher, 2, Fr::zero(), 2, vec![Fr::zero()]).unwrap();
+
+        assert_eq!(
+            imt.zeroes()[1],
+            fr("14744269619966411208579211824598458697587494354926760081771325075741142829156")
+        );
+        assert_eq!(
+            imt.root(),
+            Some(fr(
+                "7423237065226347324353380772367382631490014989348495481811164164159255474657"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_poseidon_invalid_arity() {
+        assert_eq!(PoseidonHasher::new(1).err(), Some(IMTError::InvalidArity));
+        assert_eq!(PoseidonHasher::new(17).err(), Some(IMTError::InvalidArity));
+    }
+
+    #[test]
+    fn should_not_create_tree_with_another_arity() {
+        let hasher = PoseidonHasher::new(2).unwrap();
+        let leaves = vec![Fr::from(1u64)];
+
+        assert_eq!(
+            IMT::new(hasher.clone(), 2, Fr::zero(), 3, leaves).err(),
+            Some(IMTError::InvalidArity)
+        );
+        assert_eq!(
+            FrontierIMT::new(hasher, 2, Fr::zero(), 3).err(),
+            Some(IMTError::InvalidArity)
+        );
+    }
+
+    #[test]
+    fn test_keccak256_packed_pair() {
+        // keccak256(abi.encodePacked(bytes32(uint256(1)), bytes32(uint256(2))))
+        assert_eq!(
+            keccak256_packed_hash_function(vec!["1".to_string(), "0x02".to_string()]),
+            "e90b7bceb6e7df5418fb78d8ee546e97c83a08bbccc01a0644d599ccd2a7c2e0"
+        );
+    }
+
+    #[test]
+    fn test_keccak256_packed_zero_hashes() {
+        // Zero hashes `Z_1..Z_4` and the empty depth-32 root of the Nomad/Hyperlane `MerkleLib`
+        // contract, which hashes nodes with `keccak256(abi.encodePacked(left, right))`.
+        let expected = [
+            "ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5",
+            "b4c11951957c6f8f642c4af61cd6b24640fec6dc7fc607ee8206a99e92410d30",
+            "21ddb9a356815c3fac1026b6dec5df3124afbadb485c9ba5a3e3398a04b7ba85",
+            "e58769b32a1beaf1ea27375a44095a0d1fb664ce2dd358e7fcbfb78c26a19344",
+        ];
+
+        let imt = IMT::new(Keccak256Hasher, 5, [0u8; 32], 2, vec![]).unwrap();
+        let zeroes = imt.zeroes();
+
+        for (level, zero) in expected.iter().enumerate() {
+            assert_eq!(hex::encode(zeroes[level + 1]), *zero);
+        }
+
+        let imt = IMT::new(
+            keccak256_packed_hash_function,
+            32,
+            "0".to_string(),
+            2,
+            vec!["0".to_string()],
+        )
+        .unwrap();
+
+        assert_eq!(
+            imt.root(),
+            Some("27ae5ba08d7291c96c8cbddcc148bf48a6d68c7974b94356f53754ef6171d757".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_keccak256_packed_invalid_node() {
+        keccak256_packed_hash_function(vec!["zz".to_string()]);
+    }
+}
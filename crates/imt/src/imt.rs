@@ -1,148 +1,761 @@
-pub struct IMT {
-    nodes: Vec<Vec<IMTNode>>,
-    zeroes: Vec<IMTNode>,
-    hash: IMTHashFunction,
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+
+use crate::hash::Hasher;
+use crate::store::{IMTStore, MemoryStore};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IMTError {
+    TreeFull { capacity: usize },
+    LeafIndexOutOfRange { index: usize, len: usize },
+    TooManyLeaves,
+    InvalidArity,
+    InvalidDepth,
+    NoLeaves,
+    InvalidFilledSubtrees,
+    InvalidTreeSizes { old_size: usize, new_size: usize },
+    MalformedProof(&'static str),
+    LeafNotFound,
+    UnknownCheckpoint,
+    Io(std::io::ErrorKind),
+    TruncatedData,
+    UnsupportedVersion(u8),
+    CorruptedData(&'static str),
+    RootMismatch,
+    StorePoisoned,
+}
+
+impl fmt::Display for IMTError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IMTError::TreeFull { capacity } => {
+                write!(f, "The tree is full, it contains {} leaves", capacity)
+            },
+            IMTError::LeafIndexOutOfRange { index, len } => {
+                write!(
+                    f,
+                    "The leaf {} does not exist in this tree of {} leaves",
+                    index, len
+                )
+            },
+            IMTError::TooManyLeaves => {
+                write!(f, "The tree cannot contain more than arity^depth leaves")
+            },
+            IMTError::InvalidArity => write!(f, "The arity of the tree is not supported"),
+            IMTError::InvalidDepth => write!(f, "The depth of the tree is not supported"),
+            IMTError::NoLeaves => write!(f, "There are no leaves to add"),
+            IMTError::InvalidFilledSubtrees => write!(
+                f,
+                "The filled subtrees do not match the depth and the arity of the tree"
+            ),
+            IMTError::InvalidTreeSizes { old_size, new_size } => write!(
+                f,
+                "A tree of {} leaves cannot be an extension of a tree of {} leaves",
+                new_size, old_size
+            ),
+            IMTError::MalformedProof(reason) => write!(f, "Malformed proof: {}", reason),
+            IMTError::LeafNotFound => write!(f, "The leaf does not exist in this tree"),
+            IMTError::UnknownCheckpoint => {
+                write!(f, "The checkpoint does not exist or was discarded")
+            },
+            IMTError::Io(kind) => write!(f, "I/O error: {}", kind),
+            IMTError::TruncatedData => write!(f, "The serialized tree is truncated"),
+            IMTError::UnsupportedVersion(version) => {
+                write!(f, "The serialization version {} is not supported", version)
+            },
+            IMTError::CorruptedData(reason) => {
+                write!(f, "The serialized tree is corrupted: {}", reason)
+            },
+            IMTError::RootMismatch => write!(
+                f,
+                "The stored root does not match the root recomputed from the tree"
+            ),
+            IMTError::StorePoisoned => write!(
+                f,
+                "A previous commit of the store failed, it must be opened again"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IMTError {}
+
+/// Incremental Merkle Tree of fixed depth and arity, where empty positions hold zero values.
+///
+/// The nodes are kept in an [`IMTStore`], in memory by default. Accessors borrowing the nodes,
+/// such as [`IMT::leaves`] or [`IMT::level`], are only available for the in-memory store. With
+/// other stores, reading a node can fail, so [`IMT::try_nodes`] is used instead of
+/// [`IMT::nodes`].
+pub struct IMT<H: Hasher = IMTHashFunction, S: IMTStore<H::Node> = MemoryStore<<H as Hasher>::Node>>
+{
+    store: S,
+    root: Option<H::Node>,
+    zeroes: Vec<H::Node>,
+    hash: H,
     depth: usize,
     arity: usize,
+    root_history: VecDeque<(H::Node, usize)>,
+    root_history_size: usize,
+    leaf_index: Option<HashMap<H::Node, BTreeSet<usize>>>,
+    checkpoints: Vec<Checkpoint<H::Node>>,
+    journal: Vec<(usize, usize, Option<H::Node>)>,
+    next_checkpoint_id: usize,
+}
+
+/// Identifier of a state of an [`IMT`] that can be restored with [`IMT::rollback`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CheckpointId(usize);
+
+struct Checkpoint<N> {
+    id: CheckpointId,
+    journal_len: usize,
+    root_history: VecDeque<(N, usize)>,
 }
 
-pub struct IMTMerkleProof {
-    root: IMTNode,
-    leaf: IMTNode,
+/// A Merkle proof of an [`IMT`] leaf.
+///
+/// With the `serde` feature, it serializes to the same JSON object as the `MerkleProof` of the
+/// `@zk-kit/imt` JS package, i.e. with `root`, `leaf`, `pathIndices` and `siblings` fields.
+/// Deserialized proofs are validated as in [`IMTMerkleProof::new`].
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase", try_from = "IMTMerkleProofParts<N>")
+)]
+pub struct IMTMerkleProof<N = IMTNode> {
+    root: N,
+    leaf: N,
     path_indices: Vec<usize>,
-    siblings: Vec<Vec<IMTNode>>,
+    siblings: Vec<Vec<N>>,
 }
 
 pub type IMTNode = String;
 pub type IMTHashFunction = fn(Vec<IMTNode>) -> IMTNode;
 
-impl IMT {
+/// Unvalidated fields of a deserialized [`IMTMerkleProof`].
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IMTMerkleProofParts<N> {
+    root: N,
+    leaf: N,
+    path_indices: Vec<usize>,
+    siblings: Vec<Vec<N>>,
+}
+
+#[cfg(feature = "serde")]
+impl<N> TryFrom<IMTMerkleProofParts<N>> for IMTMerkleProof<N> {
+    type Error = IMTError;
+
+    fn try_from(parts: IMTMerkleProofParts<N>) -> Result<Self, Self::Error> {
+        IMTMerkleProof::new(parts.root, parts.leaf, parts.path_indices, parts.siblings)
+    }
+}
+
+impl<N> IMTMerkleProof<N> {
+    /// Creates a proof from its parts, checking that they describe a well-formed path.
+    ///
+    /// There must be one path index per level, every level must have the same number of
+    /// siblings (`arity - 1`) and every path index must be lower than the arity.
     pub fn new(
-        hash: IMTHashFunction,
+        root: N,
+        leaf: N,
+        path_indices: Vec<usize>,
+        siblings: Vec<Vec<N>>,
+    ) -> Result<IMTMerkleProof<N>, IMTError> {
+        let proof = IMTMerkleProof {
+            root,
+            leaf,
+            path_indices,
+            siblings,
+        };
+
+        proof.validate()?;
+
+        Ok(proof)
+    }
+
+    pub fn root(&self) -> &N {
+        &self.root
+    }
+
+    pub fn leaf(&self) -> &N {
+        &self.leaf
+    }
+
+    pub fn path_indices(&self) -> &[usize] {
+        &self.path_indices
+    }
+
+    pub fn siblings(&self) -> &[Vec<N>] {
+        &self.siblings
+    }
+
+    fn validate(&self) -> Result<(), IMTError> {
+        if self.path_indices.len() != self.siblings.len() {
+            return Err(IMTError::MalformedProof(
+                "The proof must have one path index per level of siblings",
+            ));
+        }
+
+        let siblings_per_level = self.siblings.first().map_or(1, Vec::len);
+
+        if siblings_per_level == 0
+            || self
+                .siblings
+                .iter()
+                .any(|level| level.len() != siblings_per_level)
+        {
+            return Err(IMTError::MalformedProof(
+                "Each level of the proof must have arity - 1 siblings",
+            ));
+        }
+
+        if self
+            .path_indices
+            .iter()
+            .any(|&index| index > siblings_per_level)
+        {
+            return Err(IMTError::MalformedProof(
+                "The path indices must be lower than the arity",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<H: Hasher> IMT<H> {
+    pub fn new(
+        hash: H,
         depth: usize,
-        zero_value: IMTNode,
+        zero_value: H::Node,
         arity: usize,
-        leaves: Vec<IMTNode>,
-    ) -> Result<IMT, &'static str> {
-        if leaves.len() > arity.pow(depth as u32) {
-            return Err("The tree cannot contain more than arity^depth leaves");
+        leaves: Vec<H::Node>,
+    ) -> Result<IMT<H>, IMTError> {
+        let mut imt = IMT::with_store(hash, depth, zero_value, arity, MemoryStore::new())?;
+
+        if !leaves.is_empty() {
+            imt.insert_many(leaves)?;
+        }
+
+        Ok(imt)
+    }
+
+    pub fn nodes(&self) -> Vec<Vec<H::Node>> {
+        (0..=self.depth)
+            .map(|level| self.level(level).to_vec())
+            .collect()
+    }
+
+    pub fn leaves(&self) -> &[H::Node] {
+        self.store.level(0)
+    }
+
+    /// Returns the filled nodes of a level, the leaves being level 0 and the root level `depth`.
+    pub fn level(&self, level: usize) -> &[H::Node] {
+        self.store.level(level)
+    }
+
+    /// Returns the node at the given position, or the zero of its level if it is not filled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the level is greater than the depth of the tree.
+    pub fn node(&self, level: usize, index: usize) -> &H::Node {
+        self.level(level).get(index).unwrap_or(&self.zeroes[level])
+    }
+
+    /// Iterates over the filled nodes as `(level, index, node)`, level by level from the leaves.
+    pub fn iter_nodes(&self) -> impl Iterator<Item = (usize, usize, &H::Node)> {
+        (0..=self.depth).flat_map(move |level| {
+            self.level(level)
+                .iter()
+                .enumerate()
+                .map(move |(index, node)| (level, index, node))
+        })
+    }
+
+    /// Verifies a proof against its root with the given hash function, without a tree.
+    ///
+    /// The proof must have the depth and the arity of the tree: a shorter proof would let an
+    /// internal node pass as a leaf. Malformed proofs, e.g. with a path index not lower than the
+    /// arity, are rejected.
+    pub fn verify(hash: &H, depth: usize, arity: usize, proof: &IMTMerkleProof<H::Node>) -> bool {
+        if proof.siblings.len() != depth
+            || proof.siblings.iter().any(|level| level.len() + 1 != arity)
+            || proof.validate().is_err()
+        {
+            return false;
+        }
+
+        let mut node = proof.leaf.clone();
+
+        for (i, sibling) in proof.siblings.iter().enumerate() {
+            let mut children = sibling.clone();
+            children.insert(proof.path_indices[i], node);
+
+            node = hash.hash(children);
+        }
+
+        node == proof.root
+    }
+}
+
+impl<H: Hasher, S: IMTStore<H::Node>> IMT<H, S> {
+    /// Creates a tree whose nodes are kept in the given store.
+    ///
+    /// The nodes already in the store, e.g. in a file store that is opened again, are the
+    /// initial nodes of the tree. They are not hashed again, so they must have been computed
+    /// with the same hash function, depth, zero value and arity.
+    ///
+    /// Changes are not undone when the store fails: after an error of the store, the root, the
+    /// root history and the leaf index of the tree may not match the store anymore, so the
+    /// tree must be dropped and created again from the store, e.g. reopened from its files.
+    pub fn with_store(
+        hash: H,
+        depth: usize,
+        zero_value: H::Node,
+        arity: usize,
+        store: S,
+    ) -> Result<IMT<H, S>, IMTError> {
+        let capacity = capacity(depth, arity)?;
+        check_hasher_arity(&hash, arity)?;
+
+        if store.number_of_leaves() > capacity {
+            return Err(IMTError::TooManyLeaves);
         }
 
         let mut imt = IMT {
-            nodes: vec![vec![]; depth + 1],
+            root: store.get(depth, 0)?,
+            store,
             zeroes: vec![],
             hash,
             depth,
             arity,
+            root_history: VecDeque::new(),
+            root_history_size: 0,
+            leaf_index: None,
+            checkpoints: vec![],
+            journal: vec![],
+            next_checkpoint_id: 0,
         };
 
+        // The zero of the root level is kept too, so that every position has a fallback.
         let mut current_zero = zero_value;
         for _ in 0..depth {
             imt.zeroes.push(current_zero.clone());
-            current_zero = (imt.hash)(vec![current_zero; arity]);
-        }
-
-        imt.nodes[0] = leaves;
-
-        for level in 0..depth {
-            for index in 0..((imt.nodes[level].len() as f64 / arity as f64).ceil() as usize) {
-                let position = index * arity;
-                let children: Vec<_> = (0..arity)
-                    .map(|i| {
-                        imt.nodes[level]
-                            .get(position + i)
-                            .cloned()
-                            .unwrap_or_else(|| imt.zeroes[level].clone())
-                    })
-                    .collect();
-
-                if let Some(next_level) = imt.nodes.get_mut(level + 1) {
-                    next_level.push((imt.hash)(children));
-                }
-            }
+            current_zero = imt.hash.hash(vec![current_zero; arity]);
         }
+        imt.zeroes.push(current_zero);
 
         Ok(imt)
     }
 
-    pub fn root(&mut self) -> Option<IMTNode> {
-        self.nodes[self.depth].first().cloned()
+    pub fn root(&self) -> Option<H::Node> {
+        self.root.clone()
     }
 
     pub fn depth(&self) -> usize {
         self.depth
     }
 
-    pub fn nodes(&self) -> Vec<Vec<IMTNode>> {
-        self.nodes.clone()
-    }
-
-    pub fn zeroes(&self) -> Vec<IMTNode> {
-        self.zeroes.clone()
+    /// Returns the nodes of every level, reading them from the store.
+    pub fn try_nodes(&self) -> Result<Vec<Vec<H::Node>>, IMTError> {
+        (0..=self.depth)
+            .map(|level| {
+                (0..self.level_len(level))
+                    .map(|index| self.node_or_zero(level, index))
+                    .collect()
+            })
+            .collect()
     }
 
-    pub fn leaves(&self) -> Vec<IMTNode> {
-        self.nodes[0].clone()
+    /// Returns the zero value of each level below the root, starting with the leaves.
+    pub fn zeroes(&self) -> &[H::Node] {
+        &self.zeroes[..self.depth]
     }
 
     pub fn arity(&self) -> usize {
         self.arity
     }
 
-    pub fn insert(&mut self, leaf: IMTNode) -> Result<(), &'static str> {
-        if self.nodes[0].len() >= self.arity.pow(self.depth as u32) {
-            return Err("The tree is full");
+    /// Returns the maximum number of leaves of the tree, i.e. `arity^depth`.
+    pub fn capacity(&self) -> usize {
+        self.arity.pow(self.depth as u32)
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn insert(&mut self, leaf: H::Node) -> Result<(), IMTError> {
+        let index = self.store.number_of_leaves();
+
+        if index >= self.capacity() {
+            return Err(IMTError::TreeFull {
+                capacity: self.capacity(),
+            });
         }
 
-        let index = self.nodes[0].len();
-        self.nodes[0].push(leaf);
-        self.update(index, self.nodes[0][index].clone())
+        self.set_node(0, index, leaf.clone())?;
+        self.update(index, leaf)
+    }
+
+    /// Inserts many leaves at once.
+    ///
+    /// All the leaves are appended first, then each affected level is recomputed only once, so
+    /// intermediate nodes are not rehashed for every leaf. If the leaves do not fit in the tree,
+    /// none of them is inserted. An empty list leaves the tree and its root history unchanged.
+    pub fn insert_many(&mut self, leaves: Vec<H::Node>) -> Result<(), IMTError> {
+        self.insert_leaves(leaves, hash_groups)
     }
 
-    pub fn update(&mut self, mut index: usize, new_leaf: IMTNode) -> Result<(), &'static str> {
-        if index >= self.nodes[0].len() {
-            return Err("The leaf does not exist in this tree");
+    /// Inserts the leaves as [`IMT::insert_many`], hashing the groups of children of each level
+    /// with the given function.
+    fn insert_leaves(
+        &mut self,
+        leaves: Vec<H::Node>,
+        hash_groups: HashGroups<H>,
+    ) -> Result<(), IMTError> {
+        if leaves.is_empty() {
+            return Ok(());
+        }
+
+        let start_index = self.store.number_of_leaves();
+
+        if leaves.len() > self.capacity() - start_index {
+            return Err(IMTError::TooManyLeaves);
+        }
+
+        for (i, leaf) in leaves.into_iter().enumerate() {
+            self.index_leaf(start_index + i, None, Some(&leaf));
+            self.set_node(0, start_index + i, leaf)?;
         }
 
+        self.hash_levels(start_index, hash_groups)?;
+        self.commit()?;
+        self.record_root();
+
+        Ok(())
+    }
+
+    pub fn update(&mut self, mut index: usize, new_leaf: H::Node) -> Result<(), IMTError> {
+        self.check_leaf_index(index)?;
+
+        let old_leaf = self.store.get(0, index)?;
+        self.index_leaf(index, old_leaf, Some(&new_leaf));
+
         let mut node = new_leaf;
-        self.nodes[0][index].clone_from(&node);
+        self.set_node(0, index, node.clone())?;
 
         for level in 0..self.depth {
             let position = index % self.arity;
             let level_start_index = index - position;
             let level_end_index = level_start_index + self.arity;
 
-            let children: Vec<_> = (level_start_index..level_end_index)
-                .map(|i| {
-                    self.nodes[level]
-                        .get(i)
-                        .cloned()
-                        .unwrap_or_else(|| self.zeroes[level].clone())
-                })
-                .collect();
+            let children = (level_start_index..level_end_index)
+                .map(|i| self.node_or_zero(level, i))
+                .collect::<Result<_, _>>()?;
 
-            node = (self.hash)(children);
+            node = self.hash.hash(children);
             index /= self.arity;
 
-            if self.nodes[level + 1].len() <= index {
-                self.nodes[level + 1].push(node.clone());
-            } else {
-                self.nodes[level + 1][index].clone_from(&node);
-            }
+            self.set_node(level + 1, index, node.clone())?;
         }
 
+        self.commit()?;
+        self.record_root();
+
         Ok(())
     }
 
-    pub fn delete(&mut self, index: usize) -> Result<(), &'static str> {
+    pub fn delete(&mut self, index: usize) -> Result<(), IMTError> {
         self.update(index, self.zeroes[0].clone())
     }
 
-    pub fn create_proof(&self, index: usize) -> Result<IMTMerkleProof, &'static str> {
-        if index >= self.nodes[0].len() {
-            return Err("The leaf does not exist in this tree");
+    /// Enables a reverse index from leaves to their indices, used by [`IMT::index_of`],
+    /// [`IMT::contains`] and [`IMT::create_proof_for_leaf`].
+    ///
+    /// The index is built from the current leaves and kept up to date by every change of the
+    /// tree. Without it, leaves are looked up with a linear scan.
+    pub fn enable_leaf_index(&mut self) -> Result<(), IMTError> {
+        let mut leaf_index: HashMap<_, BTreeSet<_>> = HashMap::new();
+
+        for index in 0..self.store.number_of_leaves() {
+            if let Some(leaf) = self.store.get(0, index)? {
+                leaf_index.entry(leaf).or_default().insert(index);
+            }
         }
 
+        self.leaf_index = Some(leaf_index);
+
+        Ok(())
+    }
+
+    /// Returns the lowest index of the leaf, if it is in the tree.
+    ///
+    /// Deleted leaves are zero values, so the index of the zero value can be returned.
+    pub fn index_of(&self, leaf: &H::Node) -> Result<Option<usize>, IMTError> {
+        if let Some(leaf_index) = &self.leaf_index {
+            return Ok(leaf_index
+                .get(leaf)
+                .and_then(|indices| indices.first().copied()));
+        }
+
+        for index in 0..self.store.number_of_leaves() {
+            if self.store.get(0, index)?.as_ref() == Some(leaf) {
+                return Ok(Some(index));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn contains(&self, leaf: &H::Node) -> Result<bool, IMTError> {
+        Ok(self.index_of(leaf)?.is_some())
+    }
+
+    /// Creates a proof of the leaf at the lowest index with the given value.
+    pub fn create_proof_for_leaf(
+        &self,
+        leaf: &H::Node,
+    ) -> Result<IMTMerkleProof<H::Node>, IMTError> {
+        let index = self.index_of(leaf)?.ok_or(IMTError::LeafNotFound)?;
+
+        self.create_proof(index)
+    }
+
+    /// Updates the reverse index, if enabled, when the leaf at `index` changes.
+    fn index_leaf(&mut self, index: usize, old_leaf: Option<H::Node>, new_leaf: Option<&H::Node>) {
+        let Some(leaf_index) = &mut self.leaf_index else {
+            return;
+        };
+
+        if let Some(old_leaf) = old_leaf {
+            if let Some(indices) = leaf_index.get_mut(&old_leaf) {
+                indices.remove(&index);
+
+                if indices.is_empty() {
+                    leaf_index.remove(&old_leaf);
+                }
+            }
+        }
+
+        if let Some(new_leaf) = new_leaf {
+            leaf_index
+                .entry(new_leaf.clone())
+                .or_default()
+                .insert(index);
+        }
+    }
+
+    /// Saves the current state of the tree, which can be restored with [`IMT::rollback`].
+    ///
+    /// While there are checkpoints, the previous value of every changed node is recorded, so
+    /// checkpoints that are no longer needed should be discarded with
+    /// [`IMT::discard_checkpoint`].
+    pub fn checkpoint(&mut self) -> CheckpointId {
+        let id = CheckpointId(self.next_checkpoint_id);
+        self.next_checkpoint_id += 1;
+
+        self.checkpoints.push(Checkpoint {
+            id,
+            journal_len: self.journal.len(),
+            root_history: self.root_history.clone(),
+        });
+
+        id
+    }
+
+    /// Restores the tree as it was when the checkpoint was created.
+    ///
+    /// Leaves inserted since then are removed, changed nodes get their previous value back and
+    /// the root history is restored. Later checkpoints are discarded, but the checkpoint itself
+    /// can be used again.
+    pub fn rollback(&mut self, id: CheckpointId) -> Result<(), IMTError> {
+        let position = self.checkpoint_position(id)?;
+        let checkpoint = &self.checkpoints[position];
+        let journal_len = checkpoint.journal_len;
+
+        self.root_history.clone_from(&checkpoint.root_history);
+        self.checkpoints.truncate(position + 1);
+
+        while self.journal.len() > journal_len {
+            let (level, index, previous) = self.journal.pop().expect("The journal is not empty");
+
+            if level == 0 {
+                let current = self.store.get(0, index)?;
+                self.index_leaf(index, current, previous.as_ref());
+            }
+
+            match previous {
+                Some(node) => self.store.set(level, index, node),
+                None => self.store.truncate(level, index),
+            }
+        }
+
+        self.commit()
+    }
+
+    /// Forgets the checkpoint and all the older ones, with the changes recorded for them.
+    pub fn discard_checkpoint(&mut self, id: CheckpointId) -> Result<(), IMTError> {
+        let position = self.checkpoint_position(id)?;
+        self.checkpoints.drain(..=position);
+
+        match self.checkpoints.first() {
+            Some(checkpoint) => {
+                let discarded = checkpoint.journal_len;
+                self.journal.drain(..discarded);
+
+                for checkpoint in &mut self.checkpoints {
+                    checkpoint.journal_len -= discarded;
+                }
+            },
+            None => self.journal.clear(),
+        }
+
+        Ok(())
+    }
+
+    fn checkpoint_position(&self, id: CheckpointId) -> Result<usize, IMTError> {
+        self.checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.id == id)
+            .ok_or(IMTError::UnknownCheckpoint)
+    }
+
+    /// Sets a node in the store, recording its previous value if there are checkpoints.
+    fn set_node(&mut self, level: usize, index: usize, node: H::Node) -> Result<(), IMTError> {
+        if !self.checkpoints.is_empty() {
+            self.journal
+                .push((level, index, self.store.get(level, index)?));
+        }
+
+        self.store.set(level, index, node);
+
+        Ok(())
+    }
+
+    /// Commits the changes to the store, and reads the new root.
+    fn commit(&mut self) -> Result<(), IMTError> {
+        self.store.commit()?;
+        self.root = self.store.get(self.depth, 0)?;
+
+        Ok(())
+    }
+
+    /// Enables a bounded history of the last `size` roots, starting with the current one.
+    ///
+    /// Every change of the root is recorded together with the number of leaves of the tree at
+    /// that time, so that proofs created against a recent root are still accepted after new
+    /// insertions, as the root history of Semaphore contracts does. A size of 0 disables it.
+    pub fn enable_root_history(&mut self, size: usize) {
+        self.root_history_size = size;
+        self.root_history.clear();
+        self.record_root();
+    }
+
+    /// Returns the recorded roots with the number of leaves at each, from the oldest one.
+    pub fn root_history(&self) -> Vec<(H::Node, usize)> {
+        self.root_history.iter().cloned().collect()
+    }
+
+    /// Checks if the root is the current root or one still in the root history.
+    pub fn is_known_root(&self, root: &H::Node) -> bool {
+        self.root.as_ref() == Some(root)
+            || self
+                .root_history
+                .iter()
+                .any(|(known_root, _)| known_root == root)
+    }
+
+    /// Verifies a proof created against the current root or any root in the root history.
+    pub fn verify_proof_with_root_history(&self, proof: &IMTMerkleProof<H::Node>) -> bool {
+        self.is_known_root(&proof.root) && self.verify_proof(proof)
+    }
+
+    fn record_root(&mut self) {
+        if self.root_history_size == 0 {
+            return;
+        }
+
+        if let Some(root) = self.root.clone() {
+            if self.root_history.len() == self.root_history_size {
+                self.root_history.pop_front();
+            }
+
+            self.root_history
+                .push_back((root, self.store.number_of_leaves()));
+        }
+    }
+
+    pub(crate) fn check_leaf_index(&self, index: usize) -> Result<(), IMTError> {
+        let len = self.store.number_of_leaves();
+
+        if index >= len {
+            return Err(IMTError::LeafIndexOutOfRange { index, len });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of nodes of a level, which follows from the number of leaves.
+    pub(crate) fn level_len(&self, level: usize) -> usize {
+        match self.store.number_of_leaves() {
+            0 => 0,
+            len => (len - 1) / self.arity.pow(level as u32) + 1,
+        }
+    }
+
+    /// Returns the zero of the level, including the root level.
+    pub(crate) fn zero(&self, level: usize) -> &H::Node {
+        &self.zeroes[level]
+    }
+
+    /// Returns the node at the given position, or the zero of the level if it is not filled.
+    pub(crate) fn node_or_zero(&self, level: usize, index: usize) -> Result<H::Node, IMTError> {
+        Ok(self
+            .store
+            .get(level, index)?
+            .unwrap_or_else(|| self.zeroes[level].clone()))
+    }
+
+    pub(crate) fn hasher(&self) -> &H {
+        &self.hash
+    }
+
+    /// Recomputes, level by level, all the parent nodes of the leaves from `start_index` onwards.
+    fn hash_levels(
+        &mut self,
+        mut start_index: usize,
+        hash_groups: HashGroups<H>,
+    ) -> Result<(), IMTError> {
+        for level in 0..self.depth {
+            start_index /= self.arity;
+            let end_index = self.level_len(level + 1);
+
+            let groups = (start_index..end_index)
+                .map(|index| {
+                    (index * self.arity..(index + 1) * self.arity)
+                        .map(|i| self.node_or_zero(level, i))
+                        .collect()
+                })
+                .collect::<Result<_, _>>()?;
+
+            for (index, node) in (start_index..).zip(hash_groups(&self.hash, groups)) {
+                self.set_node(level + 1, index, node)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn create_proof(&self, index: usize) -> Result<IMTMerkleProof<H::Node>, IMTError> {
+        self.check_leaf_index(index)?;
+
         let mut siblings = Vec::with_capacity(self.depth);
         let mut path_indices = Vec::with_capacity(self.depth);
         let mut current_index = index;
@@ -157,12 +770,7 @@ impl IMT {
 
             for i in level_start_index..level_end_index {
                 if i != current_index {
-                    level_siblings.push(
-                        self.nodes[level]
-                            .get(i)
-                            .cloned()
-                            .unwrap_or_else(|| self.zeroes[level].clone()),
-                    );
+                    level_siblings.push(self.node_or_zero(level, i)?);
                 }
             }
 
@@ -171,27 +779,97 @@ impl IMT {
         }
 
         Ok(IMTMerkleProof {
-            root: self.nodes[self.depth][0].clone(),
-            leaf: self.nodes[0][index].clone(),
+            root: self.node_or_zero(self.depth, 0)?,
+            leaf: self.node_or_zero(0, index)?,
             path_indices,
             siblings,
         })
     }
 
-    pub fn verify_proof(&self, proof: &IMTMerkleProof) -> bool {
-        let mut node = proof.leaf.clone();
+    pub fn verify_proof(&self, proof: &IMTMerkleProof<H::Node>) -> bool {
+        IMT::<H>::verify(&self.hash, self.depth, self.arity, proof)
+    }
+}
 
-        for (i, sibling) in proof.siblings.iter().enumerate() {
-            let mut children = sibling.clone();
-            children.insert(proof.path_indices[i], node);
+/// Parallel construction, for hashers and nodes that can be shared between threads.
+#[cfg(feature = "parallel")]
+impl<H> IMT<H>
+where
+    H: Hasher + Sync,
+    H::Node: Send + Sync,
+{
+    /// Creates a tree as [`IMT::new`], hashing the nodes of each level in parallel.
+    pub fn par_new(
+        hash: H,
+        depth: usize,
+        zero_value: H::Node,
+        arity: usize,
+        leaves: Vec<H::Node>,
+    ) -> Result<IMT<H>, IMTError> {
+        let mut imt = IMT::new(hash, depth, zero_value, arity, vec![])?;
+        imt.par_insert_many(leaves)?;
 
-            node = (self.hash)(children);
-        }
+        Ok(imt)
+    }
+}
 
-        node == proof.root
+#[cfg(feature = "parallel")]
+impl<H, S> IMT<H, S>
+where
+    H: Hasher + Sync,
+    H::Node: Send + Sync,
+    S: IMTStore<H::Node>,
+{
+    /// Inserts many leaves as [`IMT::insert_many`], hashing the nodes of each level in parallel.
+    pub fn par_insert_many(&mut self, leaves: Vec<H::Node>) -> Result<(), IMTError> {
+        self.insert_leaves(leaves, par_hash_groups)
     }
 }
 
+/// Function hashing each group of children of a level into its parent.
+type HashGroups<H> = fn(&H, Vec<Vec<<H as Hasher>::Node>>) -> Vec<<H as Hasher>::Node>;
+
+fn hash_groups<H: Hasher>(hash: &H, groups: Vec<Vec<H::Node>>) -> Vec<H::Node> {
+    groups
+        .into_iter()
+        .map(|children| hash.hash(children))
+        .collect()
+}
+
+#[cfg(feature = "parallel")]
+fn par_hash_groups<H>(hash: &H, groups: Vec<Vec<H::Node>>) -> Vec<H::Node>
+where
+    H: Hasher + Sync,
+    H::Node: Send + Sync,
+{
+    use rayon::prelude::*;
+
+    groups
+        .into_par_iter()
+        .map(|children| hash.hash(children))
+        .collect()
+}
+
+/// Checks that the hasher accepts groups of `arity` nodes, if it only accepts one size.
+pub(crate) fn check_hasher_arity<H: Hasher>(hash: &H, arity: usize) -> Result<(), IMTError> {
+    match hash.arity() {
+        Some(hasher_arity) if hasher_arity != arity => Err(IMTError::InvalidArity),
+        _ => Ok(()),
+    }
+}
+
+/// Returns `arity^depth`, the number of leaves of a tree, checking that it fits in a `usize`.
+pub(crate) fn capacity(depth: usize, arity: usize) -> Result<usize, IMTError> {
+    if arity < 2 {
+        return Err(IMTError::InvalidArity);
+    }
+
+    u32::try_from(depth)
+        .ok()
+        .and_then(|depth| arity.checked_pow(depth))
+        .ok_or(IMTError::InvalidDepth)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -245,6 +923,199 @@ mod tests {
         assert!(imt.verify_proof(&proof));
     }
 
+    #[test]
+    fn test_verify_without_tree() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let imt = IMT::new(
+            hash,
+            2,
+            "zero".to_string(),
+            3,
+            vec!["leaf1".to_string(), "leaf2".to_string()],
+        )
+        .unwrap();
+
+        let proof = imt.create_proof(1).unwrap();
+        assert_eq!(proof.leaf(), "leaf2");
+        assert_eq!(proof.path_indices(), &[1, 0]);
+        assert_eq!(proof.siblings()[0], vec!["leaf1", "zero"]);
+
+        let rebuilt = IMTMerkleProof::new(
+            proof.root().clone(),
+            proof.leaf().clone(),
+            proof.path_indices().to_vec(),
+            proof.siblings().to_vec(),
+        )
+        .unwrap();
+        assert!(IMT::verify(&hash, 2, 3, &rebuilt));
+
+        let forged = IMTMerkleProof::new(
+            proof.root().clone(),
+            "leaf3".to_string(),
+            proof.path_indices().to_vec(),
+            proof.siblings().to_vec(),
+        )
+        .unwrap();
+        assert!(!IMT::verify(&hash, 2, 3, &forged));
+    }
+
+    #[test]
+    fn should_not_create_malformed_proof() {
+        let root = "root".to_string();
+        let leaf = "leaf".to_string();
+        let siblings = vec![vec!["a".to_string()], vec!["b".to_string()]];
+
+        assert!(
+            IMTMerkleProof::new(root.clone(), leaf.clone(), vec![0], siblings.clone()).is_err()
+        );
+        assert!(
+            IMTMerkleProof::new(root.clone(), leaf.clone(), vec![0, 2], siblings.clone()).is_err()
+        );
+        assert!(IMTMerkleProof::new(
+            root.clone(),
+            leaf.clone(),
+            vec![0, 0],
+            vec![vec!["a".to_string()], vec![]]
+        )
+        .is_err());
+        assert!(IMTMerkleProof::new(root, leaf, vec![0, 1], siblings).is_ok());
+    }
+
+    #[test]
+    fn should_not_verify_proof_of_another_shape() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let imt = IMT::new(hash, 2, "zero".to_string(), 2, vec!["leaf1".to_string()]).unwrap();
+        let other = IMT::new(hash, 1, "zero".to_string(), 2, vec!["leaf1".to_string()]).unwrap();
+
+        let proof = other.create_proof(0).unwrap();
+        assert!(IMT::verify(&hash, 1, 2, &proof));
+        assert!(!IMT::verify(&hash, 2, 2, &proof));
+        assert!(!IMT::verify(&hash, 1, 3, &proof));
+        assert!(!imt.verify_proof(&proof));
+    }
+
+    #[test]
+    fn should_not_verify_internal_node_as_leaf() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let imt = IMT::new(
+            hash,
+            2,
+            "zero".to_string(),
+            2,
+            vec!["leaf1".to_string(), "leaf2".to_string()],
+        )
+        .unwrap();
+        let proof = imt.create_proof(0).unwrap();
+
+        // A proof of the parent of the first two leaves, one level shorter.
+        let truncated = IMTMerkleProof::new(
+            proof.root().clone(),
+            imt.node(1, 0).clone(),
+            proof.path_indices()[1..].to_vec(),
+            proof.siblings()[1..].to_vec(),
+        )
+        .unwrap();
+
+        assert!(IMT::verify(&hash, 1, 2, &truncated));
+        assert!(!IMT::verify(&hash, 2, 2, &truncated));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_proof_json_matches_zk_kit_js() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let imt = IMT::new(
+            hash,
+            2,
+            "zero".to_string(),
+            2,
+            vec!["leaf1".to_string(), "leaf2".to_string()],
+        )
+        .unwrap();
+
+        // Output of `JSON.stringify(tree.createProof(1))` with `@zk-kit/imt` and the same tree.
+        let js_proof = r#"{"root":"leaf1,leaf2,zero,zero","leaf":"leaf2","pathIndices":[1,0],"siblings":[["leaf1"],["zero,zero"]],"leafIndex":1}"#;
+
+        let proof: IMTMerkleProof = serde_json::from_str(js_proof).unwrap();
+        assert!(imt.verify_proof(&proof));
+
+        let json = serde_json::to_string(&imt.create_proof(1).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"root":"leaf1,leaf2,zero,zero","leaf":"leaf2","pathIndices":[1,0],"siblings":[["leaf1"],["zero,zero"]]}"#
+        );
+
+        let round_trip: IMTMerkleProof = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&round_trip).unwrap(), json);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn should_not_deserialize_malformed_proof() {
+        let json = r#"{"root":"root","leaf":"leaf","pathIndices":[2],"siblings":[["sibling"]]}"#;
+
+        assert!(serde_json::from_str::<IMTMerkleProof>(json).is_err());
+    }
+
+    #[test]
+    fn test_root_history() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let mut imt = IMT::new(hash, 2, "zero".to_string(), 2, vec!["leaf1".to_string()]).unwrap();
+        imt.enable_root_history(3);
+
+        let old_proof = imt.create_proof(0).unwrap();
+        let first_root = imt.root().unwrap();
+
+        imt.insert("leaf2".to_string()).unwrap();
+        imt.insert("leaf3".to_string()).unwrap();
+
+        assert!(imt.is_known_root(&first_root));
+        assert!(imt.verify_proof_with_root_history(&old_proof));
+        assert_eq!(
+            imt.root_history()
+                .iter()
+                .map(|(_, size)| *size)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        imt.delete(1).unwrap();
+
+        assert!(!imt.is_known_root(&first_root));
+        assert!(!imt.verify_proof_with_root_history(&old_proof));
+        assert_eq!(imt.root_history().len(), 3);
+        assert_eq!(imt.root_history().last().unwrap().0, imt.root().unwrap());
+    }
+
+    #[test]
+    fn test_insert_no_leaves() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let mut imt = IMT::new(hash, 2, "zero".to_string(), 2, vec!["leaf1".to_string()]).unwrap();
+        imt.enable_root_history(3);
+        imt.insert("leaf2".to_string()).unwrap();
+        let root = imt.root();
+        let root_history = imt.root_history();
+
+        imt.insert_many(vec![]).unwrap();
+
+        assert_eq!(imt.root(), root);
+        assert_eq!(imt.root_history(), root_history);
+    }
+
+    #[test]
+    fn test_root_history_disabled() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let mut imt = IMT::new(hash, 2, "zero".to_string(), 2, vec!["leaf1".to_string()]).unwrap();
+        let old_proof = imt.create_proof(0).unwrap();
+
+        imt.insert("leaf2".to_string()).unwrap();
+
+        assert!(imt.root_history().is_empty());
+        let root = imt.root().unwrap();
+        assert!(imt.is_known_root(&root));
+        assert!(!imt.verify_proof_with_root_history(&old_proof));
+    }
+
     #[test]
     fn should_not_initialize_with_too_many_leaves() {
         let hash: IMTHashFunction = simple_hash_function;
@@ -256,7 +1127,21 @@ mod tests {
             "leaf5".to_string(),
         ];
         let imt = IMT::new(hash, 2, "zero".to_string(), 2, leaves);
-        assert!(imt.is_err());
+        assert_eq!(imt.err(), Some(IMTError::TooManyLeaves));
+    }
+
+    #[test]
+    fn should_not_initialize_with_invalid_arity_or_depth() {
+        let hash: IMTHashFunction = simple_hash_function;
+
+        let imt = IMT::new(hash, 3, "zero".to_string(), 1, vec![]);
+        assert_eq!(imt.err(), Some(IMTError::InvalidArity));
+
+        let imt = IMT::new(hash, 3, "zero".to_string(), 0, vec![]);
+        assert_eq!(imt.err(), Some(IMTError::InvalidArity));
+
+        let imt = IMT::new(hash, 64, "zero".to_string(), 2, vec![]);
+        assert_eq!(imt.err(), Some(IMTError::InvalidDepth));
     }
 
     #[test]
@@ -272,7 +1157,53 @@ mod tests {
         .unwrap();
 
         let result = imt.insert("leaf3".to_string());
-        assert!(result.is_err());
+        assert_eq!(result, Err(IMTError::TreeFull { capacity: 2 }));
+    }
+
+    #[test]
+    fn test_insert_many() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let leaves: Vec<_> = (0..9).map(|i| format!("leaf{i}")).collect();
+
+        for arity in 2..=3usize {
+            let capacity = arity.pow(2);
+
+            for start in 0..capacity {
+                for end in start..=capacity {
+                    let initial = leaves[..start].to_vec();
+                    let mut imt =
+                        IMT::new(hash, 2, "zero".to_string(), arity, initial.clone()).unwrap();
+                    let mut expected =
+                        IMT::new(hash, 2, "zero".to_string(), arity, initial).unwrap();
+
+                    imt.insert_many(leaves[start..end].to_vec()).unwrap();
+                    for leaf in &leaves[start..end] {
+                        expected.insert(leaf.clone()).unwrap();
+                    }
+
+                    assert_eq!(imt.root(), expected.root());
+                    assert_eq!(imt.nodes(), expected.nodes());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn should_not_insert_many_in_full_tree() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let mut imt = IMT::new(hash, 2, "zero".to_string(), 2, vec!["leaf1".to_string()]).unwrap();
+        let root = imt.root();
+
+        let result = imt.insert_many(vec![
+            "leaf2".to_string(),
+            "leaf3".to_string(),
+            "leaf4".to_string(),
+            "leaf5".to_string(),
+        ]);
+
+        assert_eq!(result, Err(IMTError::TooManyLeaves));
+        assert_eq!(imt.leaves(), vec!["leaf1".to_string()]);
+        assert_eq!(imt.root(), root);
     }
 
     #[test]
@@ -281,13 +1212,17 @@ mod tests {
         let mut imt = IMT::new(hash, 3, "zero".to_string(), 2, vec!["leaf1".to_string()]).unwrap();
 
         let result = imt.delete(1);
-        assert!(result.is_err());
+        assert_eq!(
+            result,
+            Err(IMTError::LeafIndexOutOfRange { index: 1, len: 1 })
+        );
+        assert!(result.unwrap_err().to_string().contains("does not exist"));
     }
 
     #[test]
     fn test_root() {
         let hash: IMTHashFunction = simple_hash_function;
-        let mut imt = IMT::new(
+        let imt = IMT::new(
             hash,
             2,
             "zero".to_string(),
@@ -314,6 +1249,30 @@ mod tests {
         assert_eq!(imt.leaves(), vec!["leaf1".to_string(), "leaf2".to_string()]);
     }
 
+    #[test]
+    fn test_generic_hasher() {
+        struct WeightedSum {
+            weight: u64,
+        }
+
+        impl Hasher for WeightedSum {
+            type Node = u64;
+
+            fn hash(&self, nodes: Vec<u64>) -> u64 {
+                nodes.iter().fold(0, |acc, node| acc * self.weight + node)
+            }
+        }
+
+        let mut imt = IMT::new(WeightedSum { weight: 10 }, 2, 0, 2, vec![1, 2]).unwrap();
+        assert_eq!(imt.root(), Some(120));
+
+        imt.insert(3).unwrap();
+        assert_eq!(imt.root(), Some(150));
+
+        let proof = imt.create_proof(2).unwrap();
+        assert!(imt.verify_proof(&proof));
+    }
+
     #[test]
     fn test_depth_and_arity() {
         let hash: IMTHashFunction = simple_hash_function;
@@ -322,4 +1281,195 @@ mod tests {
         assert_eq!(imt.depth(), 3);
         assert_eq!(imt.arity(), 2);
     }
+
+    #[test]
+    fn test_index_of_and_contains() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let leaves = vec!["a".to_string(), "b".to_string(), "a".to_string()];
+
+        for enable_leaf_index in [false, true] {
+            let mut imt = IMT::new(hash, 3, "zero".to_string(), 2, leaves.clone()).unwrap();
+
+            if enable_leaf_index {
+                imt.enable_leaf_index().unwrap();
+            }
+
+            assert_eq!(imt.index_of(&"a".to_string()).unwrap(), Some(0));
+            assert_eq!(imt.index_of(&"b".to_string()).unwrap(), Some(1));
+            assert!(!imt.contains(&"c".to_string()).unwrap());
+
+            imt.insert("c".to_string()).unwrap();
+            assert_eq!(imt.index_of(&"c".to_string()).unwrap(), Some(3));
+
+            imt.update(0, "d".to_string()).unwrap();
+            assert_eq!(imt.index_of(&"a".to_string()).unwrap(), Some(2));
+            assert_eq!(imt.index_of(&"d".to_string()).unwrap(), Some(0));
+
+            imt.delete(2).unwrap();
+            assert!(!imt.contains(&"a".to_string()).unwrap());
+            assert_eq!(imt.index_of(&"zero".to_string()).unwrap(), Some(2));
+
+            imt.insert_many(vec!["e".to_string(), "a".to_string()])
+                .unwrap();
+            assert_eq!(imt.index_of(&"e".to_string()).unwrap(), Some(4));
+            assert_eq!(imt.index_of(&"a".to_string()).unwrap(), Some(5));
+        }
+    }
+
+    #[test]
+    fn test_create_proof_for_leaf() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let mut imt = IMT::new(
+            hash,
+            3,
+            "zero".to_string(),
+            2,
+            vec!["leaf1".to_string(), "leaf2".to_string()],
+        )
+        .unwrap();
+        imt.enable_leaf_index().unwrap();
+
+        let proof = imt.create_proof_for_leaf(&"leaf2".to_string()).unwrap();
+        assert_eq!(proof.leaf(), "leaf2");
+        assert_eq!(proof.path_indices()[0], 1);
+        assert!(imt.verify_proof(&proof));
+
+        assert_eq!(
+            imt.create_proof_for_leaf(&"leaf3".to_string()).err(),
+            Some(IMTError::LeafNotFound)
+        );
+    }
+
+    #[test]
+    fn test_borrowing_accessors() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let imt = IMT::new(
+            hash,
+            2,
+            "zero".to_string(),
+            2,
+            vec![
+                "leaf1".to_string(),
+                "leaf2".to_string(),
+                "leaf3".to_string(),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(imt.leaves(), ["leaf1", "leaf2", "leaf3"]);
+        assert_eq!(imt.level(1), ["leaf1,leaf2", "leaf3,zero"]);
+        assert_eq!(imt.level(2), [imt.root().unwrap()]);
+        assert_eq!(imt.zeroes(), ["zero", "zero,zero"]);
+
+        assert_eq!(imt.node(0, 2), "leaf3");
+        assert_eq!(imt.node(0, 3), "zero");
+        assert_eq!(imt.node(1, 1), "leaf3,zero");
+
+        let empty = IMT::new(hash, 2, "zero".to_string(), 2, vec![]).unwrap();
+        assert_eq!(empty.root(), None);
+        assert_eq!(empty.node(2, 0), "zero,zero,zero,zero");
+    }
+
+    #[test]
+    fn test_iter_nodes() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let imt = IMT::new(
+            hash,
+            2,
+            "zero".to_string(),
+            2,
+            vec![
+                "leaf1".to_string(),
+                "leaf2".to_string(),
+                "leaf3".to_string(),
+            ],
+        )
+        .unwrap();
+
+        let nodes: Vec<_> = imt.iter_nodes().collect();
+
+        assert_eq!(nodes.len(), 6);
+        assert_eq!(nodes[0], (0, 0, &"leaf1".to_string()));
+        assert_eq!(nodes[4], (1, 1, &"leaf3,zero".to_string()));
+        assert_eq!(nodes[5], (2, 0, &imt.root().unwrap()));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_build_matches_sequential_insertions() {
+        use crate::hash::PoseidonHasher;
+        use ark_bn254::Fr;
+        use ark_ff::Zero;
+
+        for arity in 2..=3usize {
+            let leaves: Vec<Fr> = (1..=100u64).map(Fr::from).collect();
+            let hasher = PoseidonHasher::new(arity).unwrap();
+            let mut imt =
+                IMT::par_new(hasher.clone(), 7, Fr::zero(), arity, leaves[..60].to_vec()).unwrap();
+            imt.par_insert_many(leaves[60..].to_vec()).unwrap();
+
+            let mut sequential = IMT::new(hasher, 7, Fr::zero(), arity, vec![]).unwrap();
+            for leaf in leaves {
+                sequential.insert(leaf).unwrap();
+            }
+
+            assert_eq!(imt.root(), sequential.root());
+            assert_eq!(imt.nodes(), sequential.nodes());
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_and_rollback() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let leaves = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut imt = IMT::new(hash, 3, "zero".to_string(), 2, leaves).unwrap();
+        imt.enable_root_history(3);
+        imt.enable_leaf_index().unwrap();
+
+        let nodes = imt.nodes();
+        let root_history = imt.root_history();
+        let checkpoint = imt.checkpoint();
+
+        imt.insert("d".to_string()).unwrap();
+        imt.update(0, "e".to_string()).unwrap();
+        imt.delete(1).unwrap();
+        imt.insert_many(vec!["f".to_string(), "g".to_string()])
+            .unwrap();
+
+        imt.rollback(checkpoint).unwrap();
+
+        assert_eq!(imt.nodes(), nodes);
+        assert_eq!(imt.root_history(), root_history);
+        assert_eq!(imt.index_of(&"a".to_string()).unwrap(), Some(0));
+        assert!(!imt.contains(&"d".to_string()).unwrap());
+
+        imt.insert("h".to_string()).unwrap();
+        imt.rollback(checkpoint).unwrap();
+        assert_eq!(imt.nodes(), nodes);
+    }
+
+    #[test]
+    fn test_nested_checkpoints() {
+        let hash: IMTHashFunction = simple_hash_function;
+        let mut imt = IMT::new(hash, 3, "zero".to_string(), 2, vec![]).unwrap();
+
+        let first = imt.checkpoint();
+        imt.insert("a".to_string()).unwrap();
+        let nodes = imt.nodes();
+        let second = imt.checkpoint();
+        imt.insert("b".to_string()).unwrap();
+        let third = imt.checkpoint();
+        imt.insert("c".to_string()).unwrap();
+
+        imt.discard_checkpoint(first).unwrap();
+        assert_eq!(imt.rollback(first), Err(IMTError::UnknownCheckpoint));
+
+        imt.rollback(second).unwrap();
+        assert_eq!(imt.nodes(), nodes);
+        assert_eq!(imt.rollback(third), Err(IMTError::UnknownCheckpoint));
+
+        imt.discard_checkpoint(second).unwrap();
+        imt.insert("d".to_string()).unwrap();
+        assert_eq!(imt.leaves(), ["a", "d"]);
+    }
 }
@@ -0,0 +1,497 @@
+use crate::hash::Hasher;
+use crate::imt::{IMTError, IMTHashFunction, IMTNode};
+
+/// Lean Incremental Merkle Tree, compatible with the LeanIMT of zk-kit's JS and Solidity packages.
+///
+/// The tree is binary and its depth grows with the number of leaves. There are no zero values:
+/// a node without a right sibling is promoted to the next level instead of being hashed.
+pub struct LeanIMT<H: Hasher = IMTHashFunction> {
+    nodes: Vec<Vec<H::Node>>,
+    hash: H,
+}
+
+pub struct LeanIMTMerkleProof<N = IMTNode> {
+    root: N,
+    leaf: N,
+    index: usize,
+    siblings: Vec<N>,
+}
+
+impl<N> LeanIMTMerkleProof<N> {
+    /// Creates a proof from its parts, checking that the index only has one bit per sibling.
+    pub fn new(
+        root: N,
+        leaf: N,
+        index: usize,
+        siblings: Vec<N>,
+    ) -> Result<LeanIMTMerkleProof<N>, IMTError> {
+        let proof = LeanIMTMerkleProof {
+            root,
+            leaf,
+            index,
+            siblings,
+        };
+
+        proof.validate()?;
+
+        Ok(proof)
+    }
+
+    pub fn root(&self) -> &N {
+        &self.root
+    }
+
+    pub fn leaf(&self) -> &N {
+        &self.leaf
+    }
+
+    /// The bits of the index are the directions of the path, starting from the leaf. Since
+    /// levels without a sibling are skipped, it can differ from the index of the leaf.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn siblings(&self) -> &[N] {
+        &self.siblings
+    }
+
+    fn validate(&self) -> Result<(), IMTError> {
+        if self
+            .index
+            .checked_shr(self.siblings.len() as u32)
+            .unwrap_or(0)
+            != 0
+        {
+            return Err(IMTError::MalformedProof(
+                "The index must not have more bits than there are siblings",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<H: Hasher> LeanIMT<H> {
+    pub fn new(hash: H, leaves: Vec<H::Node>) -> LeanIMT<H> {
+        let mut tree = LeanIMT {
+            nodes: vec![vec![]],
+            hash,
+        };
+
+        if !leaves.is_empty() {
+            tree.insert_many(leaves)
+                .expect("The list of leaves is not empty");
+        }
+
+        tree
+    }
+
+    pub fn root(&self) -> Option<H::Node> {
+        self.nodes[self.depth()].first().cloned()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    pub fn size(&self) -> usize {
+        self.nodes[0].len()
+    }
+
+    pub fn leaves(&self) -> Vec<H::Node> {
+        self.nodes[0].clone()
+    }
+
+    pub fn has(&self, leaf: &H::Node) -> bool {
+        self.index_of(leaf).is_some()
+    }
+
+    pub fn index_of(&self, leaf: &H::Node) -> Option<usize> {
+        self.nodes[0].iter().position(|node| node == leaf)
+    }
+
+    pub fn insert(&mut self, leaf: H::Node) {
+        if self.depth() < depth_for_size(self.size() + 1) {
+            self.nodes.push(vec![]);
+        }
+
+        let depth = self.depth();
+        let mut node = leaf;
+        let mut index = self.size();
+
+        for level in 0..depth {
+            set_node(&mut self.nodes[level], index, node.clone());
+
+            if index & 1 == 1 {
+                let sibling = self.nodes[level][index - 1].clone();
+                node = self.hash.hash(vec![sibling, node]);
+            }
+
+            index >>= 1;
+        }
+
+        self.nodes[depth] = vec![node];
+    }
+
+    /// Inserts many leaves at once, hashing each affected node only once.
+    pub fn insert_many(&mut self, leaves: Vec<H::Node>) -> Result<(), IMTError> {
+        if leaves.is_empty() {
+            return Err(IMTError::NoLeaves);
+        }
+
+        let mut start_index = self.size() >> 1;
+        self.nodes[0].extend(leaves);
+
+        let new_depth = depth_for_size(self.size());
+        while self.depth() < new_depth {
+            self.nodes.push(vec![]);
+        }
+
+        for level in 0..self.depth() {
+            let number_of_nodes = self.nodes[level].len().div_ceil(2);
+
+            for index in start_index..number_of_nodes {
+                let left = self.nodes[level][index * 2].clone();
+                let parent = match self.nodes[level].get(index * 2 + 1) {
+                    Some(right) => self.hash.hash(vec![left, right.clone()]),
+                    None => left,
+                };
+
+                set_node(&mut self.nodes[level + 1], index, parent);
+            }
+
+            start_index >>= 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn update(&mut self, mut index: usize, new_leaf: H::Node) -> Result<(), IMTError> {
+        self.check_leaf_index(index)?;
+
+        let depth = self.depth();
+        let mut node = new_leaf;
+
+        for level in 0..depth {
+            self.nodes[level][index].clone_from(&node);
+
+            if index & 1 == 1 {
+                let sibling = self.nodes[level][index - 1].clone();
+                node = self.hash.hash(vec![sibling, node]);
+            } else if let Some(sibling) = self.nodes[level].get(index + 1) {
+                node = self.hash.hash(vec![node, sibling.clone()]);
+            }
+
+            index >>= 1;
+        }
+
+        self.nodes[depth] = vec![node];
+
+        Ok(())
+    }
+
+    pub fn create_proof(&self, index: usize) -> Result<LeanIMTMerkleProof<H::Node>, IMTError> {
+        self.check_leaf_index(index)?;
+
+        let leaf = self.nodes[0][index].clone();
+        let mut siblings = Vec::new();
+        let mut path = 0;
+        let mut current_index = index;
+
+        for level in 0..self.depth() {
+            let is_right_node = current_index & 1;
+            let sibling_index = current_index ^ 1;
+
+            // Levels where the node is promoted have no sibling and are not part of the proof.
+            if let Some(sibling) = self.nodes[level].get(sibling_index) {
+                path |= is_right_node << siblings.len();
+                siblings.push(sibling.clone());
+            }
+
+            current_index >>= 1;
+        }
+
+        Ok(LeanIMTMerkleProof {
+            root: self.nodes[self.depth()][0].clone(),
+            leaf,
+            index: path,
+            siblings,
+        })
+    }
+
+    fn check_leaf_index(&self, index: usize) -> Result<(), IMTError> {
+        if index >= self.size() {
+            return Err(IMTError::LeafIndexOutOfRange {
+                index,
+                len: self.size(),
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn verify_proof(&self, proof: &LeanIMTMerkleProof<H::Node>) -> bool {
+        LeanIMT::verify(&self.hash, proof)
+    }
+
+    /// Verifies a proof against its root with the given hash function, without a tree, as the
+    /// static `verifyProof` of the JS package.
+    ///
+    /// Malformed proofs, with an index that has more bits than there are siblings, are rejected.
+    pub fn verify(hash: &H, proof: &LeanIMTMerkleProof<H::Node>) -> bool {
+        if proof.validate().is_err() {
+            return false;
+        }
+
+        let mut node = proof.leaf.clone();
+
+        for (i, sibling) in proof.siblings.iter().enumerate() {
+            node = if (proof.index >> i) & 1 == 1 {
+                hash.hash(vec![sibling.clone(), node])
+            } else {
+                hash.hash(vec![node, sibling.clone()])
+            };
+        }
+
+        node == proof.root
+    }
+}
+
+/// Returns the depth of a LeanIMT with `size` leaves, i.e. `ceil(log2(size))`.
+fn depth_for_size(size: usize) -> usize {
+    if size <= 1 {
+        0
+    } else {
+        (usize::BITS - (size - 1).leading_zeros()) as usize
+    }
+}
+
+fn set_node<N>(level: &mut Vec<N>, index: usize, node: N) {
+    if index < level.len() {
+        level[index] = node;
+    } else {
+        level.push(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::PoseidonHasher;
+    use crate::imt::IMT;
+    use ark_bn254::Fr;
+    use ark_ff::Zero;
+    use std::str::FromStr;
+
+    fn hash_function(nodes: Vec<String>) -> String {
+        format!("H({})", nodes.join(","))
+    }
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("leaf{i}")).collect()
+    }
+
+    #[test]
+    fn test_new_lean_imt() {
+        let hash: IMTHashFunction = hash_function;
+        let tree = LeanIMT::new(hash, vec![]);
+
+        assert_eq!(tree.root(), None);
+        assert_eq!(tree.depth(), 0);
+        assert_eq!(tree.size(), 0);
+    }
+
+    #[test]
+    fn test_root_promotes_nodes_without_siblings() {
+        let hash: IMTHashFunction = hash_function;
+
+        let tree = LeanIMT::new(hash, leaves(1));
+        assert_eq!(tree.root(), Some("leaf0".to_string()));
+
+        let tree = LeanIMT::new(hash, leaves(3));
+        assert_eq!(tree.depth(), 2);
+        assert_eq!(tree.root(), Some("H(H(leaf0,leaf1),leaf2)".to_string()));
+
+        let tree = LeanIMT::new(hash, leaves(5));
+        assert_eq!(tree.depth(), 3);
+        assert_eq!(
+            tree.root(),
+            Some("H(H(H(leaf0,leaf1),H(leaf2,leaf3)),leaf4)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_insert_matches_insert_many() {
+        let hash: IMTHashFunction = hash_function;
+
+        for size in 1..20 {
+            let mut tree = LeanIMT::new(hash, vec![]);
+            for leaf in leaves(size) {
+                tree.insert(leaf);
+            }
+
+            let mut batch_tree = LeanIMT::new(hash, vec![]);
+            let mut batch = leaves(size);
+            let second_half = batch.split_off(size / 2);
+            if !batch.is_empty() {
+                batch_tree.insert_many(batch).unwrap();
+            }
+            batch_tree.insert_many(second_half).unwrap();
+
+            assert_eq!(tree.depth(), batch_tree.depth());
+            assert_eq!(tree.root(), batch_tree.root());
+        }
+    }
+
+    #[test]
+    fn should_not_insert_empty_list_of_leaves() {
+        let hash: IMTHashFunction = hash_function;
+        let mut tree = LeanIMT::new(hash, leaves(2));
+
+        assert_eq!(tree.insert_many(vec![]), Err(IMTError::NoLeaves));
+    }
+
+    #[test]
+    fn test_update() {
+        let hash: IMTHashFunction = hash_function;
+        let mut tree = LeanIMT::new(hash, leaves(3));
+
+        tree.update(2, "new_leaf".to_string()).unwrap();
+        assert_eq!(tree.root(), Some("H(H(leaf0,leaf1),new_leaf)".to_string()));
+
+        tree.update(0, "first".to_string()).unwrap();
+        assert_eq!(tree.root(), Some("H(H(first,leaf1),new_leaf)".to_string()));
+
+        assert!(tree.update(3, "leaf3".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_has_and_index_of() {
+        let hash: IMTHashFunction = hash_function;
+        let tree = LeanIMT::new(hash, leaves(3));
+
+        assert!(tree.has(&"leaf1".to_string()));
+        assert_eq!(tree.index_of(&"leaf2".to_string()), Some(2));
+        assert!(!tree.has(&"leaf3".to_string()));
+        assert_eq!(tree.index_of(&"leaf3".to_string()), None);
+    }
+
+    #[test]
+    fn test_create_and_verify_proof() {
+        let hash: IMTHashFunction = hash_function;
+        let tree = LeanIMT::new(hash, leaves(5));
+
+        for index in 0..5 {
+            let proof = tree.create_proof(index).unwrap();
+            assert_eq!(proof.leaf(), &format!("leaf{index}"));
+            assert!(tree.verify_proof(&proof));
+        }
+
+        // The last leaf is promoted twice, so its proof only contains the root's left child.
+        let proof = tree.create_proof(4).unwrap();
+        assert_eq!(proof.siblings().len(), 1);
+        assert_eq!(proof.index(), 1);
+
+        assert!(tree.create_proof(5).is_err());
+    }
+
+    #[test]
+    fn test_verify_without_tree() {
+        let hash: IMTHashFunction = hash_function;
+        let tree = LeanIMT::new(hash, leaves(5));
+        let proof = tree.create_proof(2).unwrap();
+
+        let rebuilt = LeanIMTMerkleProof::new(
+            proof.root().clone(),
+            proof.leaf().clone(),
+            proof.index(),
+            proof.siblings().to_vec(),
+        )
+        .unwrap();
+        assert!(LeanIMT::verify(&hash, &rebuilt));
+
+        let forged = LeanIMTMerkleProof::new(
+            proof.root().clone(),
+            "leaf3".to_string(),
+            proof.index(),
+            proof.siblings().to_vec(),
+        )
+        .unwrap();
+        assert!(!LeanIMT::verify(&hash, &forged));
+
+        assert!(LeanIMTMerkleProof::new(
+            proof.root().clone(),
+            proof.leaf().clone(),
+            1 << proof.siblings().len(),
+            proof.siblings().to_vec(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_root_matches_full_imt() {
+        let values: Vec<Fr> = (1..=8u64).map(Fr::from).collect();
+        let tree = LeanIMT::new(PoseidonHasher::new(2).unwrap(), values.clone());
+        let imt = IMT::new(PoseidonHasher::new(2).unwrap(), 3, Fr::zero(), 2, values).unwrap();
+
+        assert_eq!(tree.root(), imt.root());
+    }
+
+    #[test]
+    fn test_poseidon_roots_match_js_lean_imt() {
+        let fr = |value: &str| Fr::from_str(value).unwrap();
+        let values = |n: u64| (1..=n).map(Fr::from).collect::<Vec<_>>();
+        let hash = || PoseidonHasher::new(2).unwrap();
+
+        // Roots of @zk-kit/lean-imt's `LeanIMT` with circomlibjs' `poseidon` over the leaves
+        // 1, 2, ..., n, where a node without a right sibling is promoted.
+        let roots = [
+            (1, "1"),
+            (
+                3,
+                "13816780880028945690020260331303642730075999758909899334839547418969502592169",
+            ),
+            (
+                5,
+                "11512324111804726054755717642058292259866309947044530224809882918003853859592",
+            ),
+            (
+                7,
+                "9097114702656722376419439788149110565393180352312461170314908086900836776912",
+            ),
+        ];
+
+        for (size, root) in roots {
+            let mut tree = LeanIMT::new(hash(), vec![]);
+            for value in values(size) {
+                tree.insert(value);
+            }
+            assert_eq!(tree.root(), Some(fr(root)));
+
+            let tree = LeanIMT::new(hash(), values(size));
+            assert_eq!(tree.root(), Some(fr(root)));
+        }
+
+        let mut tree = LeanIMT::new(hash(), values(3));
+        tree.insert_many((4..=7u64).map(Fr::from).collect())
+            .unwrap();
+        assert_eq!(tree.root(), Some(fr(roots[3].1)));
+
+        tree.update(6, Fr::from(42u64)).unwrap();
+        assert_eq!(
+            tree.root(),
+            Some(fr(
+                "12829947227634588323390075702657313350940517407321294828758855065381080328896"
+            ))
+        );
+
+        let mut tree = LeanIMT::new(hash(), values(5));
+        tree.update(4, Fr::from(42u64)).unwrap();
+        assert_eq!(
+            tree.root(),
+            Some(fr(
+                "11996285842255855523094789040830020723466996016181364236723381923954275763805"
+            ))
+        );
+    }
+}
@@ -1,2 +1,10 @@
+pub mod consistency;
+pub mod frontier_imt;
 pub mod hash;
 pub mod imt;
+pub mod lean_imt;
+pub mod mmr;
+pub mod multiproof;
+pub mod serialization;
+pub mod store;
+pub mod witness;
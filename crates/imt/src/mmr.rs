@@ -0,0 +1,421 @@
+use crate::hash::Hasher;
+use crate::imt::{IMTError, IMTHashFunction, IMTNode};
+
+/// Merkle Mountain Range, an append-only accumulator without a fixed capacity.
+///
+/// Leaves are grouped into perfect binary trees (the mountains) of decreasing heights, one per
+/// bit set in the number of leaves. The root bags the peaks of the mountains from right to
+/// left: `H(peak_0, H(peak_1, ... H(peak_n-2, peak_n-1)))`, and is the peak itself when there
+/// is a single mountain.
+pub struct MMR<H: Hasher = IMTHashFunction> {
+    /// Nodes of the complete subtrees of each height, the leaves being height 0.
+    nodes: Vec<Vec<H::Node>>,
+    hash: H,
+}
+
+/// Inclusion proof of a leaf of a Merkle Mountain Range.
+///
+/// `siblings` is the path from the leaf to the peak of its mountain, and `peaks` holds the
+/// peaks of the other mountains, from left to right.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MMRProof<N = IMTNode> {
+    root: N,
+    leaf: N,
+    index: usize,
+    size: usize,
+    siblings: Vec<N>,
+    peaks: Vec<N>,
+}
+
+impl<N> MMRProof<N> {
+    /// Creates a proof from its parts, checking that they match the shape of a range with
+    /// `size` leaves.
+    ///
+    /// The index must be lower than the size, there must be one sibling per level of the
+    /// mountain of the leaf and one peak per other mountain.
+    pub fn new(
+        root: N,
+        leaf: N,
+        index: usize,
+        size: usize,
+        siblings: Vec<N>,
+        peaks: Vec<N>,
+    ) -> Result<MMRProof<N>, IMTError> {
+        let proof = MMRProof {
+            root,
+            leaf,
+            index,
+            size,
+            siblings,
+            peaks,
+        };
+
+        proof.validate()?;
+
+        Ok(proof)
+    }
+
+    pub fn root(&self) -> &N {
+        &self.root
+    }
+
+    pub fn leaf(&self) -> &N {
+        &self.leaf
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The number of leaves of the range when the proof was created.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn siblings(&self) -> &[N] {
+        &self.siblings
+    }
+
+    pub fn peaks(&self) -> &[N] {
+        &self.peaks
+    }
+
+    fn validate(&self) -> Result<(), IMTError> {
+        let Some(mountain) = Mountain::of(self.index, self.size) else {
+            return Err(IMTError::MalformedProof(
+                "The index must be lower than the size",
+            ));
+        };
+
+        if self.siblings.len() != mountain.height {
+            return Err(IMTError::MalformedProof(
+                "The proof must have one sibling per level of the mountain of the leaf",
+            ));
+        }
+
+        if self.peaks.len() + 1 != self.size.count_ones() as usize {
+            return Err(IMTError::MalformedProof(
+                "The proof must have the peaks of every other mountain",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<H: Hasher> MMR<H> {
+    pub fn new(hash: H, leaves: Vec<H::Node>) -> MMR<H> {
+        let mut mmr = MMR {
+            nodes: vec![vec![]],
+            hash,
+        };
+
+        for leaf in leaves {
+            mmr.append(leaf);
+        }
+
+        mmr
+    }
+
+    /// Returns the root of the range, or `None` if it is empty.
+    pub fn root(&self) -> Option<H::Node> {
+        bag_peaks(&self.hash, self.peaks())
+    }
+
+    pub fn size(&self) -> usize {
+        self.nodes[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    pub fn leaves(&self) -> &[H::Node] {
+        &self.nodes[0]
+    }
+
+    /// Returns the peaks of the mountains, from the highest (leftmost) to the lowest.
+    pub fn peaks(&self) -> Vec<H::Node> {
+        (0..self.nodes.len())
+            .rev()
+            .filter(|&height| self.size() >> height & 1 == 1)
+            .map(|height| self.nodes[height][self.nodes[height].len() - 1].clone())
+            .collect()
+    }
+
+    /// Appends a leaf and returns its index.
+    ///
+    /// Mountains of the same height are merged, so an append hashes at most `log2(size)`
+    /// nodes.
+    pub fn append(&mut self, leaf: H::Node) -> usize {
+        let index = self.size();
+        self.nodes[0].push(leaf);
+
+        let mut height = 0;
+
+        while self.nodes[height].len() & 1 == 0 {
+            let len = self.nodes[height].len();
+            let parent = self.hash.hash(self.nodes[height][len - 2..].to_vec());
+
+            if height + 1 == self.nodes.len() {
+                self.nodes.push(vec![]);
+            }
+
+            self.nodes[height + 1].push(parent);
+            height += 1;
+        }
+
+        index
+    }
+
+    pub fn create_proof(&self, index: usize) -> Result<MMRProof<H::Node>, IMTError> {
+        let size = self.size();
+        let mountain =
+            Mountain::of(index, size).ok_or(IMTError::LeafIndexOutOfRange { index, len: size })?;
+
+        let mut position = index;
+        let siblings = (0..mountain.height)
+            .map(|height| {
+                let sibling = self.nodes[height][position ^ 1].clone();
+                position >>= 1;
+
+                sibling
+            })
+            .collect();
+
+        let mut peaks = self.peaks();
+        peaks.remove(mountain.number);
+
+        Ok(MMRProof {
+            root: self.root().expect("The range is not empty"),
+            leaf: self.nodes[0][index].clone(),
+            index,
+            size,
+            siblings,
+            peaks,
+        })
+    }
+
+    /// Verifies a proof against the current root and size of the range.
+    pub fn verify_proof(&self, proof: &MMRProof<H::Node>) -> bool {
+        MMR::verify(&self.hash, self.size(), proof)
+    }
+
+    /// Verifies a proof against its root with the given hash function, without a range.
+    ///
+    /// The proof must have the size of the range whose root is trusted: the root does not
+    /// commit to the size, so a peak of a larger range could pass as a leaf of a smaller one.
+    /// Malformed proofs, e.g. with missing peaks, are rejected.
+    pub fn verify(hash: &H, size: usize, proof: &MMRProof<H::Node>) -> bool {
+        if proof.size != size || proof.validate().is_err() {
+            return false;
+        }
+
+        let mountain = Mountain::of(proof.index, proof.size).expect("The proof is valid");
+        let mut node = proof.leaf.clone();
+
+        for (i, sibling) in proof.siblings.iter().enumerate() {
+            node = if (proof.index >> i) & 1 == 1 {
+                hash.hash(vec![sibling.clone(), node])
+            } else {
+                hash.hash(vec![node, sibling.clone()])
+            };
+        }
+
+        let mut peaks = proof.peaks.clone();
+        peaks.insert(mountain.number, node);
+
+        bag_peaks(hash, peaks).as_ref() == Some(&proof.root)
+    }
+}
+
+/// Position of a leaf in the mountains of a range.
+struct Mountain {
+    /// Position of the mountain among the peaks, from the left.
+    number: usize,
+    height: usize,
+}
+
+impl Mountain {
+    /// Returns the mountain containing the leaf at `index` in a range of `size` leaves.
+    fn of(index: usize, size: usize) -> Option<Mountain> {
+        if index >= size {
+            return None;
+        }
+
+        // The mountains are the bits set in the size, and every leaf before `index` belongs to
+        // the higher mountains, so the mountain of the leaf is the highest bit where the index
+        // and the size differ.
+        let height = (usize::BITS - 1 - (index ^ size).leading_zeros()) as usize;
+        let number = (size >> (height + 1)).count_ones() as usize;
+
+        Some(Mountain { number, height })
+    }
+}
+
+/// Bags the peaks from right to left, or returns `None` if there are none.
+fn bag_peaks<H: Hasher>(hash: &H, peaks: Vec<H::Node>) -> Option<H::Node> {
+    peaks
+        .into_iter()
+        .rev()
+        .reduce(|right, peak| hash.hash(vec![peak, right]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::PoseidonHasher;
+    use ark_bn254::Fr;
+
+    fn hash_function(nodes: Vec<String>) -> String {
+        format!("H({})", nodes.join(","))
+    }
+
+    fn leaves(size: usize) -> Vec<String> {
+        (0..size).map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn test_new_mmr() {
+        let hash: IMTHashFunction = hash_function;
+        let mmr = MMR::new(hash, vec![]);
+
+        assert!(mmr.is_empty());
+        assert_eq!(mmr.root(), None);
+        assert!(mmr.peaks().is_empty());
+    }
+
+    #[test]
+    fn test_append() {
+        let hash: IMTHashFunction = hash_function;
+        let mut mmr = MMR::new(hash, vec![]);
+
+        assert_eq!(mmr.append("0".to_string()), 0);
+        assert_eq!(mmr.root(), Some("0".to_string()));
+
+        mmr.append("1".to_string());
+        assert_eq!(mmr.peaks(), vec!["H(0,1)"]);
+
+        for leaf in leaves(7).into_iter().skip(2) {
+            mmr.append(leaf);
+        }
+
+        assert_eq!(mmr.size(), 7);
+        assert_eq!(mmr.peaks(), vec!["H(H(0,1),H(2,3))", "H(4,5)", "6"]);
+        assert_eq!(
+            mmr.root(),
+            Some("H(H(H(0,1),H(2,3)),H(H(4,5),6))".to_string())
+        );
+        assert_eq!(mmr.leaves(), leaves(7));
+    }
+
+    #[test]
+    fn test_create_and_verify_proofs() {
+        let hash: IMTHashFunction = hash_function;
+
+        for size in 1..=20 {
+            let mmr = MMR::new(hash, leaves(size));
+
+            for index in 0..size {
+                let proof = mmr.create_proof(index).unwrap();
+
+                assert_eq!(proof.leaf(), &index.to_string());
+                assert!(mmr.verify_proof(&proof));
+                assert!(MMR::verify(&hash, size, &proof));
+            }
+        }
+    }
+
+    #[test]
+    fn test_proof_shape() {
+        let hash: IMTHashFunction = hash_function;
+        let mmr = MMR::new(hash, leaves(7));
+        let proof = mmr.create_proof(5).unwrap();
+
+        assert_eq!(proof.siblings(), ["4"]);
+        assert_eq!(proof.peaks(), ["H(H(0,1),H(2,3))", "6"]);
+        assert_eq!(proof.size(), 7);
+    }
+
+    #[test]
+    fn should_not_verify_wrong_proofs() {
+        let hash: IMTHashFunction = hash_function;
+        let mmr = MMR::new(hash, leaves(6));
+        let proof = mmr.create_proof(2).unwrap();
+
+        let wrong_leaf = MMRProof::new(
+            proof.root().clone(),
+            "7".to_string(),
+            proof.index(),
+            proof.size(),
+            proof.siblings().to_vec(),
+            proof.peaks().to_vec(),
+        )
+        .unwrap();
+        assert!(!mmr.verify_proof(&wrong_leaf));
+
+        // The same leaf in a range of a different size has a different root.
+        let mut larger = MMR::new(hash, leaves(6));
+        larger.append("6".to_string());
+        assert!(!larger.verify_proof(&MMRProof {
+            root: larger.root().unwrap(),
+            ..proof.clone()
+        }));
+
+        assert_eq!(
+            mmr.create_proof(6).err(),
+            Some(IMTError::LeafIndexOutOfRange { index: 6, len: 6 })
+        );
+    }
+
+    #[test]
+    fn should_not_verify_peak_as_leaf() {
+        let hash: IMTHashFunction = hash_function;
+        let mmr = MMR::new(hash, leaves(3));
+
+        // The peak `H(0,1)` as the first leaf of a range of 2 leaves gives the same root.
+        let forged = MMRProof::new(
+            mmr.root().unwrap(),
+            "H(0,1)".to_string(),
+            0,
+            2,
+            vec!["2".to_string()],
+            vec![],
+        )
+        .unwrap();
+
+        assert!(MMR::verify(&hash, 2, &forged));
+        assert!(!MMR::verify(&hash, 3, &forged));
+        assert!(!mmr.verify_proof(&forged));
+    }
+
+    #[test]
+    fn should_not_create_malformed_proofs() {
+        let node = || "0".to_string();
+
+        assert!(MMRProof::new(node(), node(), 3, 3, vec![], vec![node()]).is_err());
+        assert!(MMRProof::new(node(), node(), 2, 3, vec![node()], vec![node()]).is_err());
+        assert!(MMRProof::new(node(), node(), 2, 3, vec![], vec![]).is_err());
+        assert!(MMRProof::new(node(), node(), 2, 3, vec![], vec![node()]).is_ok());
+    }
+
+    #[test]
+    fn test_poseidon_mmr() {
+        let hasher = PoseidonHasher::new(2).unwrap();
+        let mut mmr = MMR::new(hasher.clone(), vec![]);
+
+        for i in 0..11u64 {
+            mmr.append(Fr::from(i));
+        }
+
+        let peaks = mmr.peaks();
+        assert_eq!(peaks.len(), 3);
+        assert_eq!(
+            mmr.root(),
+            Some(hasher.hash(vec![peaks[0], hasher.hash(vec![peaks[1], peaks[2]])]))
+        );
+
+        let proof = mmr.create_proof(9).unwrap();
+        assert!(MMR::verify(&hasher, 11, &proof));
+    }
+}
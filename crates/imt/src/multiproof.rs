@@ -0,0 +1,359 @@
+use crate::hash::Hasher;
+use crate::imt::{capacity, IMTError, IMTNode, IMT};
+use crate::store::IMTStore;
+
+/// A Merkle proof of several leaves of an [`IMT`].
+///
+/// The paths of the leaves are merged: a node is part of the proof only if it cannot be computed
+/// from the leaves or from other nodes of the proof, and shared nodes are included only once.
+/// The nodes are ordered level by level, from the leaves to the root, and by index within a
+/// level, which is the order in which the verifier consumes them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IMTMultiProof<N = IMTNode> {
+    root: N,
+    depth: usize,
+    arity: usize,
+    leaf_indices: Vec<usize>,
+    leaves: Vec<N>,
+    nodes: Vec<N>,
+}
+
+impl<N> IMTMultiProof<N> {
+    /// Creates a multiproof from its parts, checking that the leaf indices are sorted, unique
+    /// and within the capacity of the tree, and that there is one leaf per index.
+    pub fn new(
+        root: N,
+        depth: usize,
+        arity: usize,
+        leaf_indices: Vec<usize>,
+        leaves: Vec<N>,
+        nodes: Vec<N>,
+    ) -> Result<IMTMultiProof<N>, IMTError> {
+        let proof = IMTMultiProof {
+            root,
+            depth,
+            arity,
+            leaf_indices,
+            leaves,
+            nodes,
+        };
+
+        proof.validate()?;
+
+        Ok(proof)
+    }
+
+    pub fn root(&self) -> &N {
+        &self.root
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn leaf_indices(&self) -> &[usize] {
+        &self.leaf_indices
+    }
+
+    pub fn leaves(&self) -> &[N] {
+        &self.leaves
+    }
+
+    pub fn nodes(&self) -> &[N] {
+        &self.nodes
+    }
+
+    fn validate(&self) -> Result<(), IMTError> {
+        let capacity = capacity(self.depth, self.arity)?;
+
+        if self.leaf_indices.is_empty() {
+            return Err(IMTError::NoLeaves);
+        }
+
+        if self.leaf_indices.len() != self.leaves.len() {
+            return Err(IMTError::MalformedProof(
+                "The proof must have one leaf per leaf index",
+            ));
+        }
+
+        if self.leaf_indices.windows(2).any(|pair| pair[0] >= pair[1])
+            || self.leaf_indices[self.leaf_indices.len() - 1] >= capacity
+        {
+            return Err(IMTError::MalformedProof(
+                "The leaf indices must be sorted, unique and lower than arity^depth",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<H: Hasher, S: IMTStore<H::Node>> IMT<H, S> {
+    /// Creates a proof of the leaves at the given indices, in any order.
+    pub fn create_multiproof(&self, indices: &[usize]) -> Result<IMTMultiProof<H::Node>, IMTError> {
+        let mut leaf_indices = indices.to_vec();
+        leaf_indices.sort_unstable();
+        leaf_indices.dedup();
+
+        if leaf_indices.is_empty() {
+            return Err(IMTError::NoLeaves);
+        }
+
+        for &index in &leaf_indices {
+            self.check_leaf_index(index)?;
+        }
+
+        let arity = self.arity();
+        let mut known = leaf_indices.clone();
+        let mut nodes = Vec::new();
+
+        for level in 0..self.depth() {
+            let mut parents = Vec::new();
+            let mut k = 0;
+
+            while k < known.len() {
+                let parent = known[k] / arity;
+
+                for child in parent * arity..(parent + 1) * arity {
+                    if known.get(k) == Some(&child) {
+                        k += 1;
+                    } else {
+                        nodes.push(self.node_or_zero(level, child)?);
+                    }
+                }
+
+                parents.push(parent);
+            }
+
+            known = parents;
+        }
+
+        Ok(IMTMultiProof {
+            root: self.node_or_zero(self.depth(), 0)?,
+            depth: self.depth(),
+            arity,
+            leaves: leaf_indices
+                .iter()
+                .map(|&index| self.node_or_zero(0, index))
+                .collect::<Result<_, _>>()?,
+            leaf_indices,
+            nodes,
+        })
+    }
+}
+
+impl<H: Hasher> IMT<H> {
+    /// Verifies a multiproof against its root with the given hash function, without a tree.
+    ///
+    /// The proof is rejected if it is malformed, if it does not contain exactly the nodes
+    /// needed to compute the root, or if its depth or arity are not the expected ones: with a
+    /// shorter depth, internal nodes or the root itself would pass as leaves.
+    pub fn verify_multiproof(
+        hash: &H,
+        depth: usize,
+        arity: usize,
+        proof: &IMTMultiProof<H::Node>,
+    ) -> bool {
+        if proof.depth != depth || proof.arity != arity || proof.validate().is_err() {
+            return false;
+        }
+
+        let mut known: Vec<_> = proof
+            .leaf_indices
+            .iter()
+            .copied()
+            .zip(proof.leaves.iter().cloned())
+            .collect();
+        let mut nodes = proof.nodes.iter();
+
+        for _ in 0..proof.depth {
+            let mut parents = Vec::new();
+            let mut known_nodes = known.into_iter().peekable();
+
+            while let Some(&(index, _)) = known_nodes.peek() {
+                let parent = index / arity;
+                let mut children = Vec::with_capacity(arity);
+
+                for child in parent * arity..(parent + 1) * arity {
+                    match known_nodes.next_if(|(index, _)| *index == child) {
+                        Some((_, node)) => children.push(node),
+                        None => match nodes.next() {
+                            Some(node) => children.push(node.clone()),
+                            None => return false,
+                        },
+                    }
+                }
+
+                parents.push((parent, hash.hash(children)));
+            }
+
+            known = parents;
+        }
+
+        nodes.next().is_none() && known.len() == 1 && known[0].1 == proof.root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imt::IMTHashFunction;
+
+    fn hash_function(nodes: Vec<String>) -> String {
+        format!("H({})", nodes.join(","))
+    }
+
+    fn tree(arity: usize, size: usize) -> IMT {
+        let leaves = (0..size).map(|i| format!("leaf{i}")).collect();
+        let hash: IMTHashFunction = hash_function;
+        IMT::new(hash, 3, "0".to_string(), arity, leaves).unwrap()
+    }
+
+    #[test]
+    fn test_create_and_verify_multiproof() {
+        let hash: IMTHashFunction = hash_function;
+
+        for arity in 2..=3 {
+            let size = arity * arity + 1;
+            let imt = tree(arity, size);
+
+            // Every subset of the leaves.
+            for subset in 1..(1u32 << size) {
+                let indices: Vec<_> = (0..size).filter(|i| subset & (1 << i) != 0).collect();
+                let proof = imt.create_multiproof(&indices).unwrap();
+
+                assert_eq!(proof.leaf_indices(), indices);
+                assert!(IMT::verify_multiproof(&hash, 3, arity, &proof));
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiproof_shares_nodes() {
+        let imt = tree(2, 8);
+
+        // Leaves 0 and 1 share all their ancestors: only the siblings of their parent and
+        // grandparent are needed, instead of 3 siblings for each leaf.
+        let proof = imt.create_multiproof(&[1, 0, 1]).unwrap();
+        assert_eq!(proof.leaf_indices(), &[0, 1]);
+        assert_eq!(proof.nodes().len(), 2);
+
+        let proof = imt.create_multiproof(&[0, 2, 4, 6]).unwrap();
+        assert_eq!(proof.nodes().len(), 4);
+
+        let proof = imt.create_multiproof(&(0..8).collect::<Vec<_>>()).unwrap();
+        assert!(proof.nodes().is_empty());
+    }
+
+    #[test]
+    fn should_not_verify_invalid_multiproof() {
+        let hash: IMTHashFunction = hash_function;
+        let imt = tree(2, 6);
+        let proof = imt.create_multiproof(&[1, 4]).unwrap();
+
+        let mut leaves = proof.leaves().to_vec();
+        leaves[1] = "leaf5".to_string();
+        let forged = IMTMultiProof::new(
+            proof.root().clone(),
+            proof.depth(),
+            proof.arity(),
+            proof.leaf_indices().to_vec(),
+            leaves,
+            proof.nodes().to_vec(),
+        )
+        .unwrap();
+        assert!(!IMT::verify_multiproof(&hash, 3, 2, &forged));
+
+        let mut nodes = proof.nodes().to_vec();
+        nodes.push("0".to_string());
+        let too_many_nodes = IMTMultiProof::new(
+            proof.root().clone(),
+            proof.depth(),
+            proof.arity(),
+            proof.leaf_indices().to_vec(),
+            proof.leaves().to_vec(),
+            nodes,
+        )
+        .unwrap();
+        assert!(!IMT::verify_multiproof(&hash, 3, 2, &too_many_nodes));
+
+        let too_few_nodes = IMTMultiProof::new(
+            proof.root().clone(),
+            proof.depth(),
+            proof.arity(),
+            proof.leaf_indices().to_vec(),
+            proof.leaves().to_vec(),
+            proof.nodes()[1..].to_vec(),
+        )
+        .unwrap();
+        assert!(!IMT::verify_multiproof(&hash, 3, 2, &too_few_nodes));
+    }
+
+    #[test]
+    fn should_not_verify_multiproof_of_another_shape() {
+        let hash: IMTHashFunction = hash_function;
+        let imt = tree(2, 6);
+        let proof = imt.create_multiproof(&[1, 4]).unwrap();
+
+        assert!(IMT::verify_multiproof(&hash, 3, 2, &proof));
+        assert!(!IMT::verify_multiproof(&hash, 2, 2, &proof));
+        assert!(!IMT::verify_multiproof(&hash, 3, 3, &proof));
+
+        // The root as the only leaf of a tree of depth 0.
+        let root_as_leaf = IMTMultiProof::new(
+            proof.root().clone(),
+            0,
+            2,
+            vec![0],
+            vec![proof.root().clone()],
+            vec![],
+        )
+        .unwrap();
+        assert!(IMT::verify_multiproof(&hash, 0, 2, &root_as_leaf));
+        assert!(!IMT::verify_multiproof(&hash, 3, 2, &root_as_leaf));
+
+        // The parent of the leaves 4 and 5 as a leaf of a tree of depth 2.
+        let node_as_leaf = IMTMultiProof::new(
+            proof.root().clone(),
+            2,
+            2,
+            vec![2],
+            vec![imt.node(1, 2).clone()],
+            vec![imt.node(1, 3).clone(), imt.node(2, 0).clone()],
+        )
+        .unwrap();
+        assert!(IMT::verify_multiproof(&hash, 2, 2, &node_as_leaf));
+        assert!(!IMT::verify_multiproof(&hash, 3, 2, &node_as_leaf));
+    }
+
+    #[test]
+    fn should_not_create_invalid_multiproof() {
+        let imt = tree(2, 3);
+
+        assert_eq!(imt.create_multiproof(&[]), Err(IMTError::NoLeaves));
+        assert_eq!(
+            imt.create_multiproof(&[0, 3]),
+            Err(IMTError::LeafIndexOutOfRange { index: 3, len: 3 })
+        );
+
+        let root = "root".to_string();
+        let leaf = "leaf".to_string();
+        assert!(IMTMultiProof::new(
+            root.clone(),
+            3,
+            2,
+            vec![1, 0],
+            vec![leaf.clone(); 2],
+            vec![]
+        )
+        .is_err());
+        assert!(
+            IMTMultiProof::new(root.clone(), 3, 2, vec![8], vec![leaf.clone()], vec![]).is_err()
+        );
+        assert!(IMTMultiProof::new(root, 3, 2, vec![0, 1], vec![leaf], vec![]).is_err());
+    }
+}
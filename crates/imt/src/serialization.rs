@@ -0,0 +1,523 @@
+use std::io::{self, Read, Write};
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use tiny_keccak::{Hasher as _, Keccak};
+
+use crate::hash::Hasher;
+use crate::imt::{capacity, check_hasher_arity, IMTError, IMTNode, IMT};
+use crate::store::IMTStore;
+
+/// Bytes at the start of every serialized tree.
+const MAGIC: [u8; 4] = *b"IMT\0";
+
+/// Version of the binary format written by [`IMT::save`].
+const VERSION: u8 = 1;
+
+/// Flag set when the internal nodes are stored after the leaves.
+const INTERNAL_NODES_FLAG: u8 = 1;
+
+/// Highest arity of the trees that can be saved, so that loading a corrupted arity cannot
+/// allocate groups of billions of nodes.
+pub const MAX_ARITY: usize = 1 << 16;
+
+/// Conversion of tree nodes to and from bytes, used by [`IMT::save`] and [`IMT::load`].
+pub trait NodeEncoding: Sized {
+    fn encode(&self) -> Vec<u8>;
+
+    /// Decodes a node, or returns `None` if the bytes are not a valid encoding.
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+impl NodeEncoding for IMTNode {
+    fn encode(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+impl NodeEncoding for [u8; 32] {
+    fn encode(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok()
+    }
+}
+
+/// Field elements are encoded as 32 little-endian bytes, and only canonical encodings are
+/// accepted.
+impl NodeEncoding for Fr {
+    fn encode(&self) -> Vec<u8> {
+        self.into_bigint().to_bytes_le()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let node = Fr::from_le_bytes_mod_order(bytes);
+
+        (bytes.len() == 32 && node.encode() == bytes).then_some(node)
+    }
+}
+
+impl<H: Hasher, S: IMTStore<H::Node>> IMT<H, S>
+where
+    H::Node: NodeEncoding,
+{
+    /// Writes the whole tree in a versioned binary format.
+    ///
+    /// The depth, the arity, the zeroes of every level up to the root, the leaves and the root
+    /// are always written, followed by a Keccak-256 checksum of all the previous bytes. The root
+    /// history is not saved, and trees of an arity higher than [`MAX_ARITY`] cannot be saved.
+    ///
+    /// The internal nodes are only written if `include_nodes` is true, for other readers of the
+    /// format that need them without hashing the leaves. They do not make [`IMT::load`] faster:
+    /// it always recomputes the internal nodes, and only checks the saved ones against them.
+    pub fn save<W: Write>(&self, writer: W, include_nodes: bool) -> Result<(), IMTError> {
+        if self.arity() > MAX_ARITY {
+            return Err(IMTError::InvalidArity);
+        }
+
+        let mut writer = ChecksumWriter::new(writer);
+        let flags = if include_nodes {
+            INTERNAL_NODES_FLAG
+        } else {
+            0
+        };
+
+        writer.write(&MAGIC)?;
+        writer.write(&[VERSION, flags])?;
+        writer.write_u32(self.depth())?;
+        writer.write_u32(self.arity())?;
+
+        for level in 0..=self.depth() {
+            writer.write_node(self.zero(level))?;
+        }
+
+        writer.write_u64(self.level_len(0))?;
+        let levels = if include_nodes { self.depth() } else { 0 };
+
+        for level in 0..=levels {
+            for index in 0..self.level_len(level) {
+                writer.write_node(&self.node_or_zero(level, index)?)?;
+            }
+        }
+
+        match self.root() {
+            Some(root) => {
+                writer.write(&[1])?;
+                writer.write_node(&root)?;
+            },
+            None => writer.write(&[0])?,
+        }
+
+        writer.finish()
+    }
+}
+
+impl<H: Hasher> IMT<H>
+where
+    H::Node: NodeEncoding,
+{
+    /// Reads a tree written by [`IMT::save`], with the hash function it was built with.
+    ///
+    /// The checksum and the zeroes are checked first. Then the tree is rebuilt from its leaves,
+    /// and its root must match the stored one. If the internal nodes were saved, every level
+    /// must match the rebuilt one too.
+    ///
+    /// The saved internal nodes are never used as they are: the checksum can be recomputed by
+    /// anyone, so they could be forged, and checking each of them against its children hashes
+    /// as many nodes as rebuilding the tree. Loading a tree therefore costs one hash per
+    /// internal node, with or without them.
+    ///
+    /// The arity must not be higher than [`MAX_ARITY`], and the number of leaves is not trusted
+    /// to allocate memory: a corrupted count fails when the input ends.
+    pub fn load<R: Read>(hash: H, reader: R) -> Result<IMT<H>, IMTError> {
+        let mut reader = ChecksumReader::new(reader);
+
+        if reader.read_array::<4>()? != MAGIC {
+            return Err(IMTError::CorruptedData("invalid magic bytes"));
+        }
+
+        let [version, flags] = reader.read_array::<2>()?;
+        if version != VERSION {
+            return Err(IMTError::UnsupportedVersion(version));
+        }
+        if flags & !INTERNAL_NODES_FLAG != 0 {
+            return Err(IMTError::CorruptedData("unknown flags"));
+        }
+
+        let depth = reader.read_u32()?;
+        let arity = reader.read_u32()?;
+        let capacity = capacity(depth, arity)?;
+
+        if arity > MAX_ARITY {
+            return Err(IMTError::InvalidArity);
+        }
+
+        check_hasher_arity(&hash, arity)?;
+
+        let zeroes: Vec<H::Node> = (0..=depth)
+            .map(|_| reader.read_node())
+            .collect::<Result<_, _>>()?;
+
+        let number_of_leaves = reader.read_u64()?;
+        if number_of_leaves > capacity {
+            return Err(IMTError::TooManyLeaves);
+        }
+
+        let mut nodes = vec![reader.read_nodes(number_of_leaves)?];
+
+        if flags & INTERNAL_NODES_FLAG != 0 {
+            for level in 1..=depth {
+                let number_of_nodes = nodes[level - 1].len().div_ceil(arity);
+
+                nodes.push(reader.read_nodes(number_of_nodes)?);
+            }
+        }
+
+        let root = match reader.read_array::<1>()? {
+            [0] => None,
+            [1] => Some(reader.read_node()?),
+            _ => return Err(IMTError::CorruptedData("invalid root marker")),
+        };
+
+        reader.finish()?;
+
+        for level in 1..=depth {
+            if zeroes[level] != hash.hash(vec![zeroes[level - 1].clone(); arity]) {
+                return Err(IMTError::CorruptedData(
+                    "the zeroes do not match the hash function",
+                ));
+            }
+        }
+
+        let saved_nodes = nodes.split_off(1);
+        let zero_value = zeroes[0].clone();
+        let tree = IMT::new(hash, depth, zero_value, arity, nodes.remove(0))?;
+
+        if saved_nodes
+            .iter()
+            .enumerate()
+            .any(|(level, nodes)| tree.level(level + 1) != nodes.as_slice())
+        {
+            return Err(IMTError::CorruptedData(
+                "the internal nodes do not match the leaves",
+            ));
+        }
+
+        if tree.root() != root {
+            return Err(IMTError::RootMismatch);
+        }
+
+        Ok(tree)
+    }
+}
+
+pub(crate) fn io_error(error: io::Error) -> IMTError {
+    match error.kind() {
+        io::ErrorKind::UnexpectedEof => IMTError::TruncatedData,
+        kind => IMTError::Io(kind),
+    }
+}
+
+/// Writes bytes while hashing them, and ends with their checksum.
+struct ChecksumWriter<W> {
+    writer: W,
+    keccak: Keccak,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn new(writer: W) -> Self {
+        ChecksumWriter {
+            writer,
+            keccak: Keccak::v256(),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), IMTError> {
+        self.keccak.update(bytes);
+        self.writer.write_all(bytes).map_err(io_error)
+    }
+
+    fn write_u32(&mut self, value: usize) -> Result<(), IMTError> {
+        let value = u32::try_from(value).map_err(|_| IMTError::Io(io::ErrorKind::InvalidInput))?;
+        self.write(&value.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, value: usize) -> Result<(), IMTError> {
+        self.write(&(value as u64).to_le_bytes())
+    }
+
+    /// Writes a node prefixed with the length of its encoding.
+    fn write_node<N: NodeEncoding>(&mut self, node: &N) -> Result<(), IMTError> {
+        let bytes = node.encode();
+
+        self.write_u32(bytes.len())?;
+        self.write(&bytes)
+    }
+
+    fn finish(mut self) -> Result<(), IMTError> {
+        let mut checksum = [0u8; 32];
+        self.keccak.finalize(&mut checksum);
+
+        self.writer.write_all(&checksum).map_err(io_error)?;
+        self.writer.flush().map_err(io_error)
+    }
+}
+
+/// Reads bytes while hashing them, and ends by checking their checksum.
+struct ChecksumReader<R> {
+    reader: R,
+    keccak: Keccak,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    fn new(reader: R) -> Self {
+        ChecksumReader {
+            reader,
+            keccak: Keccak::v256(),
+        }
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], IMTError> {
+        let mut bytes = [0u8; N];
+
+        self.reader.read_exact(&mut bytes).map_err(io_error)?;
+        self.keccak.update(&bytes);
+
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<usize, IMTError> {
+        Ok(u32::from_le_bytes(self.read_array()?) as usize)
+    }
+
+    fn read_u64(&mut self) -> Result<usize, IMTError> {
+        usize::try_from(u64::from_le_bytes(self.read_array()?)).map_err(|_| IMTError::TooManyLeaves)
+    }
+
+    fn read_node<N: NodeEncoding>(&mut self) -> Result<N, IMTError> {
+        let len = self.read_u32()?;
+        let mut bytes = Vec::new();
+
+        // The length is not trusted to preallocate, since the data may be corrupted.
+        (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut bytes)
+            .map_err(io_error)?;
+
+        if bytes.len() != len {
+            return Err(IMTError::TruncatedData);
+        }
+
+        self.keccak.update(&bytes);
+
+        N::decode(&bytes).ok_or(IMTError::CorruptedData("invalid node encoding"))
+    }
+
+    /// Reads `count` nodes, without preallocating them, so that the memory used is bounded by
+    /// the length of the input.
+    fn read_nodes<N: NodeEncoding>(&mut self, count: usize) -> Result<Vec<N>, IMTError> {
+        let mut nodes = Vec::new();
+
+        for _ in 0..count {
+            nodes.push(self.read_node()?);
+        }
+
+        Ok(nodes)
+    }
+
+    fn finish(mut self) -> Result<(), IMTError> {
+        let mut checksum = [0u8; 32];
+        let mut expected = [0u8; 32];
+
+        self.reader.read_exact(&mut expected).map_err(io_error)?;
+        self.keccak.finalize(&mut checksum);
+
+        if checksum != expected {
+            return Err(IMTError::CorruptedData("checksum mismatch"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::PoseidonHasher;
+    use crate::imt::IMTHashFunction;
+    use ark_ff::Zero;
+
+    fn hash_function(nodes: Vec<String>) -> String {
+        format!("H({})", nodes.join(","))
+    }
+
+    fn tree(arity: usize, number_of_leaves: usize) -> IMT {
+        let hash: IMTHashFunction = hash_function;
+        let leaves = (0..number_of_leaves).map(|i| format!("leaf{i}")).collect();
+
+        IMT::new(hash, 3, "0".to_string(), arity, leaves).unwrap()
+    }
+
+    fn save(tree: &IMT, include_nodes: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        tree.save(&mut bytes, include_nodes).unwrap();
+
+        bytes
+    }
+
+    fn load(bytes: &[u8]) -> Result<IMT, IMTError> {
+        let hash: IMTHashFunction = hash_function;
+        IMT::load(hash, bytes)
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        for arity in 2..=3usize {
+            for number_of_leaves in [0, 1, 5, arity.pow(3)] {
+                let tree = tree(arity, number_of_leaves);
+
+                for include_nodes in [false, true] {
+                    let mut loaded = load(&save(&tree, include_nodes)).unwrap();
+
+                    assert_eq!(loaded.nodes(), tree.nodes());
+                    assert_eq!(loaded.zeroes(), tree.zeroes());
+                    assert_eq!(loaded.arity(), arity);
+                    assert_eq!(loaded.depth(), 3);
+
+                    if number_of_leaves < arity.pow(3) {
+                        loaded.insert("new_leaf".to_string()).unwrap();
+                        assert_eq!(loaded.leaves().len(), number_of_leaves + 1);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_poseidon_tree() {
+        let values: Vec<Fr> = (1..=5u64).map(Fr::from).collect();
+        let tree = IMT::new(PoseidonHasher::new(2).unwrap(), 4, Fr::zero(), 2, values).unwrap();
+
+        let mut bytes = Vec::new();
+        tree.save(&mut bytes, true).unwrap();
+        let loaded = IMT::load(PoseidonHasher::new(2).unwrap(), &bytes[..]).unwrap();
+
+        assert_eq!(loaded.root(), tree.root());
+        assert_eq!(loaded.nodes(), tree.nodes());
+    }
+
+    #[test]
+    fn should_not_load_truncated_data() {
+        let bytes = save(&tree(2, 5), true);
+
+        for len in 0..bytes.len() {
+            assert_eq!(load(&bytes[..len]).err(), Some(IMTError::TruncatedData));
+        }
+    }
+
+    #[test]
+    fn should_not_load_corrupted_data() {
+        for include_nodes in [false, true] {
+            let bytes = save(&tree(2, 5), include_nodes);
+
+            for i in 0..bytes.len() {
+                let mut corrupted = bytes.clone();
+                corrupted[i] ^= 1;
+
+                assert!(load(&corrupted).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn should_not_load_forged_internal_nodes() {
+        let mut bytes = save(&tree(2, 5), true);
+
+        // Replaces the first node of level 1, and recomputes the checksum.
+        let node = [&14u32.to_le_bytes()[..], b"H(leaf0,leaf1)"].concat();
+        let position = bytes.windows(node.len()).position(|w| w == node).unwrap();
+        bytes[position + node.len() - 2] = b'9';
+
+        let checksum_position = bytes.len() - 32;
+        let mut keccak = Keccak::v256();
+        keccak.update(&bytes[..checksum_position]);
+        keccak.finalize(&mut bytes[checksum_position..]);
+
+        assert_eq!(
+            load(&bytes).err(),
+            Some(IMTError::CorruptedData(
+                "the internal nodes do not match the leaves"
+            ))
+        );
+    }
+
+    #[test]
+    fn should_not_load_oversized_trees() {
+        let header = |depth: u32, arity: u32| {
+            [
+                &MAGIC[..],
+                &[VERSION, 0],
+                &depth.to_le_bytes(),
+                &arity.to_le_bytes(),
+            ]
+            .concat()
+        };
+
+        // A single level of 4 billion nodes, whose zero would be hashed before the checksum
+        // could be checked.
+        let bytes = header(1, 4_000_000_000);
+        assert_eq!(load(&bytes).err(), Some(IMTError::InvalidArity));
+
+        // 2^40 leaves announced, but the input ends after the zeroes.
+        let mut bytes = header(40, 2);
+        for _ in 0..=40 {
+            bytes.extend_from_slice(&1u32.to_le_bytes());
+            bytes.extend_from_slice(b"0");
+        }
+        bytes.extend_from_slice(&(1u64 << 40).to_le_bytes());
+        assert_eq!(load(&bytes).err(), Some(IMTError::TruncatedData));
+
+        let hash: IMTHashFunction = hash_function;
+        let tree = IMT::new(hash, 1, "0".to_string(), MAX_ARITY + 1, vec![]).unwrap();
+        assert_eq!(
+            tree.save(&mut Vec::new(), false).err(),
+            Some(IMTError::InvalidArity)
+        );
+    }
+
+    #[test]
+    fn should_not_load_with_hasher_of_another_arity() {
+        let values: Vec<Fr> = (1..=5u64).map(Fr::from).collect();
+        let tree = IMT::new(PoseidonHasher::new(2).unwrap(), 4, Fr::zero(), 2, values).unwrap();
+
+        let mut bytes = Vec::new();
+        tree.save(&mut bytes, false).unwrap();
+
+        assert_eq!(
+            IMT::load(PoseidonHasher::new(3).unwrap(), &bytes[..]).err(),
+            Some(IMTError::InvalidArity)
+        );
+    }
+
+    #[test]
+    fn should_not_load_unsupported_version() {
+        let mut bytes = save(&tree(2, 5), false);
+        bytes[4] = 2;
+
+        assert_eq!(load(&bytes).err(), Some(IMTError::UnsupportedVersion(2)));
+    }
+
+    #[test]
+    fn should_not_load_with_another_hash_function() {
+        let bytes = save(&tree(2, 5), false);
+        let hash: IMTHashFunction = |nodes| format!("G({})", nodes.join(","));
+
+        assert!(IMT::load(hash, &bytes[..]).is_err());
+    }
+}
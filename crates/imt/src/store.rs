@@ -0,0 +1,788 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use tiny_keccak::{Hasher as _, Keccak};
+
+use crate::imt::IMTError;
+use crate::serialization::{io_error, NodeEncoding};
+
+/// Length of a log entry that truncates a level instead of setting a node.
+const TRUNCATION: u32 = u32::MAX;
+
+/// Length of a record of an index file: the position of the node in the log and its length.
+const INDEX_RECORD_LEN: u64 = 12;
+
+const LOG_FILE: &str = "nodes.log";
+
+/// File with the length of the log whose batches are applied to the index files.
+const APPLIED_FILE: &str = "applied";
+
+/// Storage of the nodes of an [`IMT`](crate::imt::IMT), addressed by level and index.
+///
+/// Level 0 contains the leaves. The tree only sets a node at an index lower than or equal to
+/// the number of nodes of its level, so levels grow from left to right. The changes of each
+/// tree operation are followed by a call to [`IMTStore::commit`].
+pub trait IMTStore<N> {
+    /// Returns the node at the given position, or `None` if it was never set. Stores that read
+    /// their nodes back, e.g. from a file, return an error if they cannot.
+    fn get(&self, level: usize, index: usize) -> Result<Option<N>, IMTError>;
+
+    fn set(&mut self, level: usize, index: usize, node: N);
+
+    /// Removes the nodes of the level from index `len` onwards.
+    fn truncate(&mut self, level: usize, len: usize);
+
+    fn number_of_leaves(&self) -> usize;
+
+    /// Makes all the changes since the last commit durable at once.
+    ///
+    /// The changes are already visible before the commit, and they are not undone if it fails.
+    fn commit(&mut self) -> Result<(), IMTError>;
+}
+
+/// Store keeping every level in a `Vec`, used by default.
+#[derive(Clone, Debug)]
+pub struct MemoryStore<N> {
+    nodes: Vec<Vec<N>>,
+}
+
+impl<N> MemoryStore<N> {
+    pub fn new() -> MemoryStore<N> {
+        MemoryStore { nodes: vec![] }
+    }
+
+    pub(crate) fn level(&self, level: usize) -> &[N] {
+        self.nodes.get(level).map_or(&[], Vec::as_slice)
+    }
+}
+
+impl<N> Default for MemoryStore<N> {
+    fn default() -> Self {
+        MemoryStore::new()
+    }
+}
+
+impl<N: Clone> IMTStore<N> for MemoryStore<N> {
+    fn get(&self, level: usize, index: usize) -> Result<Option<N>, IMTError> {
+        Ok(self
+            .nodes
+            .get(level)
+            .and_then(|nodes| nodes.get(index))
+            .cloned())
+    }
+
+    fn set(&mut self, level: usize, index: usize, node: N) {
+        if self.nodes.len() <= level {
+            self.nodes.resize_with(level + 1, Vec::new);
+        }
+
+        let nodes = &mut self.nodes[level];
+
+        if index < nodes.len() {
+            nodes[index] = node;
+        } else {
+            nodes.push(node);
+        }
+    }
+
+    fn truncate(&mut self, level: usize, len: usize) {
+        if let Some(nodes) = self.nodes.get_mut(level) {
+            nodes.truncate(len);
+        }
+    }
+
+    fn number_of_leaves(&self) -> usize {
+        self.nodes.first().map_or(0, Vec::len)
+    }
+
+    fn commit(&mut self) -> Result<(), IMTError> {
+        Ok(())
+    }
+}
+
+/// Store keeping the nodes in a directory, with an append-only log of their values and one
+/// index file per level.
+///
+/// Each commit appends one batch with all the nodes set since the previous commit to the log,
+/// followed by a Keccak-256 checksum, and syncs it. The batch is then applied to the index
+/// files, whose fixed-width records give the position of each node in the log, and the length
+/// of the log whose batches are applied is saved. Only the number of nodes of each level is
+/// kept in memory: nodes are read from the index and the log when needed.
+///
+/// When the directory is opened, the batches that were not applied yet, at most one after a
+/// crash, are applied again. An incomplete batch at the end of the log, left by a crash during a
+/// commit, is discarded, but a batch that does not match its checksum anywhere else is an error.
+///
+/// If a commit fails, the log and the index files may not match the store anymore, so every
+/// later read or commit fails with [`IMTError::StorePoisoned`] until the store is opened again.
+///
+/// Entries of a batch are encoded as `level: u32`, `index: u64`, `length: u32` and the
+/// [`NodeEncoding`] of the node, in little-endian. A length of `u32::MAX`, without a node,
+/// removes the nodes of the level from the index onwards. Index records are encoded as
+/// `position: u64` and `length: u32`, in little-endian.
+pub struct FileStore<N> {
+    path: PathBuf,
+    log: File,
+    log_len: u64,
+    indexes: Vec<File>,
+    level_lens: Vec<usize>,
+    pending: HashMap<(usize, usize), N>,
+    pending_truncations: Vec<(usize, usize)>,
+    poisoned: bool,
+    #[cfg(test)]
+    fail_before_apply: bool,
+}
+
+impl<N: NodeEncoding> FileStore<N> {
+    /// Opens the store in the given directory, or creates it if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileStore<N>, IMTError> {
+        let path = path.as_ref().to_path_buf();
+        fs::create_dir_all(&path).map_err(io_error)?;
+
+        let log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path.join(LOG_FILE))
+            .map_err(io_error)?;
+
+        let mut store = FileStore {
+            path,
+            log,
+            log_len: 0,
+            indexes: vec![],
+            level_lens: vec![],
+            pending: HashMap::new(),
+            pending_truncations: vec![],
+            poisoned: false,
+            #[cfg(test)]
+            fail_before_apply: false,
+        };
+
+        while store.index_path(store.indexes.len()).exists() {
+            store.open_index(store.indexes.len())?;
+        }
+
+        store.log_len = store.applied_len()?;
+        store.recover()?;
+
+        for index in &store.indexes {
+            let len = index.metadata().map_err(io_error)?.len();
+
+            if len % INDEX_RECORD_LEN != 0 {
+                return Err(IMTError::CorruptedData("truncated index record"));
+            }
+
+            store.level_lens.push((len / INDEX_RECORD_LEN) as usize);
+        }
+
+        Ok(store)
+    }
+
+    /// Applies the batches written after the last applied one, and discards an incomplete batch
+    /// at the end of the log.
+    fn recover(&mut self) -> Result<(), IMTError> {
+        let file_len = self.log.metadata().map_err(io_error)?.len();
+
+        if self.log_len > file_len {
+            return Err(IMTError::CorruptedData(
+                "the log is shorter than its applied batches",
+            ));
+        }
+
+        let mut log = &self.log;
+        log.seek(SeekFrom::Start(self.log_len)).map_err(io_error)?;
+        let mut reader = BufReader::new(log);
+        let mut batches = vec![];
+        let mut position = self.log_len;
+
+        loop {
+            match read_batch(&mut reader, position, file_len)? {
+                Batch::Complete(payload) => {
+                    let len = payload.len() as u64 + 40;
+                    batches.push((position + 8, payload));
+                    position += len;
+                },
+                Batch::Torn => {
+                    self.log.set_len(position).map_err(io_error)?;
+                    break;
+                },
+                Batch::End => break,
+            }
+        }
+
+        for (payload_position, payload) in &batches {
+            self.apply(payload, *payload_position)?;
+        }
+
+        if position != self.log_len {
+            self.log_len = position;
+            self.save_applied_len()?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies a batch to the index files, and syncs them.
+    fn apply(&mut self, payload: &[u8], payload_position: u64) -> Result<(), IMTError> {
+        let mut offset = 0;
+        let mut changed_levels = BTreeSet::new();
+
+        while offset < payload.len() {
+            let entry = payload
+                .get(offset..offset + 16)
+                .ok_or(IMTError::CorruptedData("truncated log entry"))?;
+            let level = u32::from_le_bytes(entry[..4].try_into().unwrap()) as usize;
+            let index = u64::from_le_bytes(entry[4..12].try_into().unwrap());
+            let len = u32::from_le_bytes(entry[12..].try_into().unwrap());
+
+            offset += 16;
+            self.open_levels(level)?;
+            changed_levels.insert(level);
+
+            let file = &self.indexes[level];
+
+            if len == TRUNCATION {
+                let file_len = file.metadata().map_err(io_error)?.len();
+                file.set_len(file_len.min(index * INDEX_RECORD_LEN))
+                    .map_err(io_error)?;
+                continue;
+            }
+
+            if payload.len() - offset < len as usize {
+                return Err(IMTError::CorruptedData("truncated log entry"));
+            }
+
+            let mut record = [0u8; INDEX_RECORD_LEN as usize];
+            record[..8].copy_from_slice(&(payload_position + offset as u64).to_le_bytes());
+            record[8..].copy_from_slice(&len.to_le_bytes());
+
+            let mut file = file;
+            file.seek(SeekFrom::Start(index * INDEX_RECORD_LEN))
+                .and_then(|_| file.write_all(&record))
+                .map_err(io_error)?;
+
+            offset += len as usize;
+        }
+
+        for level in changed_levels {
+            self.indexes[level].sync_data().map_err(io_error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the length of the log whose batches are applied to the index files.
+    fn applied_len(&self) -> Result<u64, IMTError> {
+        match fs::read(self.path.join(APPLIED_FILE)) {
+            Ok(bytes) => Ok(u64::from_le_bytes(bytes.try_into().map_err(|_| {
+                IMTError::CorruptedData("invalid length of the applied batches")
+            })?)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(error) => Err(io_error(error)),
+        }
+    }
+
+    /// Saves the length of the applied batches, replacing the previous one atomically.
+    fn save_applied_len(&self) -> Result<(), IMTError> {
+        let path = self.path.join(APPLIED_FILE);
+        let temporary_path = path.with_extension("tmp");
+        let mut file = File::create(&temporary_path).map_err(io_error)?;
+
+        file.write_all(&self.log_len.to_le_bytes())
+            .and_then(|_| file.sync_data())
+            .and_then(|_| fs::rename(&temporary_path, &path))
+            .map_err(io_error)
+    }
+
+    fn index_path(&self, level: usize) -> PathBuf {
+        self.path.join(format!("level-{level}.index"))
+    }
+
+    fn open_index(&mut self, level: usize) -> Result<(), IMTError> {
+        let index = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.index_path(level))
+            .map_err(io_error)?;
+
+        self.indexes.push(index);
+
+        Ok(())
+    }
+
+    /// Opens the index files of all the levels up to the given one.
+    fn open_levels(&mut self, level: usize) -> Result<(), IMTError> {
+        while self.indexes.len() <= level {
+            self.open_index(self.indexes.len())?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a committed node from the index of its level and the log.
+    fn read_node(&self, level: usize, index: usize) -> Result<N, IMTError> {
+        let mut record = [0u8; INDEX_RECORD_LEN as usize];
+        read_at(
+            &self.indexes[level],
+            index as u64 * INDEX_RECORD_LEN,
+            &mut record,
+        )?;
+
+        let position = u64::from_le_bytes(record[..8].try_into().unwrap());
+        let len = u32::from_le_bytes(record[8..].try_into().unwrap());
+
+        if position.saturating_add(len as u64) > self.log_len {
+            return Err(IMTError::CorruptedData(
+                "the index points outside of the log",
+            ));
+        }
+
+        let mut bytes = vec![0u8; len as usize];
+        read_at(&self.log, position, &mut bytes)?;
+
+        N::decode(&bytes).ok_or(IMTError::CorruptedData("invalid node encoding"))
+    }
+}
+
+impl<N: Clone + NodeEncoding> IMTStore<N> for FileStore<N> {
+    fn get(&self, level: usize, index: usize) -> Result<Option<N>, IMTError> {
+        if self.poisoned {
+            return Err(IMTError::StorePoisoned);
+        }
+
+        if index >= self.level_lens.get(level).copied().unwrap_or(0) {
+            return Ok(None);
+        }
+
+        match self.pending.get(&(level, index)) {
+            Some(node) => Ok(Some(node.clone())),
+            None => self.read_node(level, index).map(Some),
+        }
+    }
+
+    fn set(&mut self, level: usize, index: usize, node: N) {
+        if self.level_lens.len() <= level {
+            self.level_lens.resize(level + 1, 0);
+        }
+
+        self.level_lens[level] = self.level_lens[level].max(index + 1);
+        self.pending.insert((level, index), node);
+    }
+
+    fn truncate(&mut self, level: usize, len: usize) {
+        if let Some(level_len) = self.level_lens.get_mut(level) {
+            for index in len..*level_len {
+                self.pending.remove(&(level, index));
+            }
+
+            *level_len = (*level_len).min(len);
+            self.pending_truncations.push((level, len));
+        }
+    }
+
+    fn number_of_leaves(&self) -> usize {
+        self.level_lens.first().copied().unwrap_or(0)
+    }
+
+    fn commit(&mut self) -> Result<(), IMTError> {
+        if self.poisoned {
+            return Err(IMTError::StorePoisoned);
+        }
+
+        if self.pending.is_empty() && self.pending_truncations.is_empty() {
+            return Ok(());
+        }
+
+        let mut entries: Vec<_> = self.pending.iter().collect();
+        entries.sort_by_key(|(&position, _)| position);
+
+        let mut payload = Vec::new();
+
+        // Nodes set before a truncation were removed from the pending ones, so truncations can
+        // be applied before all the nodes of the batch.
+        for &(level, len) in &self.pending_truncations {
+            payload.extend_from_slice(&(level as u32).to_le_bytes());
+            payload.extend_from_slice(&(len as u64).to_le_bytes());
+            payload.extend_from_slice(&TRUNCATION.to_le_bytes());
+        }
+
+        for (&(level, index), node) in entries {
+            let bytes = node.encode();
+
+            payload.extend_from_slice(&(level as u32).to_le_bytes());
+            payload.extend_from_slice(&(index as u64).to_le_bytes());
+            payload.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            payload.extend_from_slice(&bytes);
+        }
+
+        let mut batch = Vec::with_capacity(payload.len() + 40);
+        batch.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        batch.extend_from_slice(&payload);
+        batch.extend_from_slice(&checksum(&payload));
+
+        // Until the commit succeeds, a failure leaves a partial batch in the log or index records
+        // of a batch that is not marked as applied, which only opening the store again repairs.
+        self.poisoned = true;
+
+        let payload_position = self.log_len + 8;
+        self.log.write_all(&batch).map_err(io_error)?;
+        self.log.sync_data().map_err(io_error)?;
+        self.log_len += batch.len() as u64;
+
+        #[cfg(test)]
+        if self.fail_before_apply {
+            return Err(IMTError::Io(io::ErrorKind::Other));
+        }
+
+        // Once the batch is in the log, it is applied again on the next opening if a crash
+        // happens before the length of the applied batches is saved.
+        self.apply(&payload, payload_position)?;
+        self.save_applied_len()?;
+
+        self.pending.clear();
+        self.pending_truncations.clear();
+        self.poisoned = false;
+
+        Ok(())
+    }
+}
+
+/// Result of reading the next batch of the log.
+enum Batch {
+    Complete(Vec<u8>),
+    /// An incomplete batch at the end of the log.
+    Torn,
+    End,
+}
+
+/// Reads the payload of the batch at `position`, in a log of `file_len` bytes.
+///
+/// A batch that does not match its checksum is torn if it ends the log, and corrupted if it is
+/// followed by other bytes.
+fn read_batch<R: Read>(reader: &mut R, position: u64, file_len: u64) -> Result<Batch, IMTError> {
+    let mut len = [0u8; 8];
+    match read_full(reader, &mut len)? {
+        0 => return Ok(Batch::End),
+        read if read < len.len() => return Ok(Batch::Torn),
+        _ => {},
+    }
+
+    let len = u64::from_le_bytes(len);
+    let mut payload = Vec::new();
+    reader
+        .take(len)
+        .read_to_end(&mut payload)
+        .map_err(io_error)?;
+
+    let mut expected = [0u8; 32];
+    if (payload.len() as u64) < len || read_full(reader, &mut expected)? < expected.len() {
+        return Ok(Batch::Torn);
+    }
+
+    if checksum(&payload) != expected {
+        if position + len + 40 == file_len {
+            return Ok(Batch::Torn);
+        }
+
+        return Err(IMTError::CorruptedData("checksum mismatch of a log batch"));
+    }
+
+    Ok(Batch::Complete(payload))
+}
+
+/// Reads as many bytes as possible into the buffer, and returns how many were read.
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<usize, IMTError> {
+    let mut read = 0;
+
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]).map_err(io_error)? {
+            0 => break,
+            n => read += n,
+        }
+    }
+
+    Ok(read)
+}
+
+fn read_at(mut file: &File, position: u64, buffer: &mut [u8]) -> Result<(), IMTError> {
+    file.seek(SeekFrom::Start(position))
+        .and_then(|_| file.read_exact(buffer))
+        .map_err(io_error)
+}
+
+fn checksum(bytes: &[u8]) -> [u8; 32] {
+    let mut keccak = Keccak::v256();
+    let mut result = [0u8; 32];
+
+    keccak.update(bytes);
+    keccak.finalize(&mut result);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imt::{IMTHashFunction, IMT};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn hash_function(nodes: Vec<String>) -> String {
+        format!("H({})", nodes.join(","))
+    }
+
+    fn store_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("zk-kit-imt-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+
+        path
+    }
+
+    fn append_to_log(path: &Path, bytes: &[u8]) {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(path.join(LOG_FILE))
+            .unwrap();
+
+        file.write_all(bytes).unwrap();
+    }
+
+    fn file_tree(path: &Path) -> IMT<IMTHashFunction, FileStore<String>> {
+        let hash: IMTHashFunction = hash_function;
+        let store = FileStore::open(path).unwrap();
+
+        IMT::with_store(hash, 3, "0".to_string(), 2, store).unwrap()
+    }
+
+    #[test]
+    fn test_file_store_matches_memory_store() {
+        let path = store_path("matches");
+        let hash: IMTHashFunction = hash_function;
+        let mut tree = file_tree(&path);
+        let mut imt = IMT::new(hash, 3, "0".to_string(), 2, vec![]).unwrap();
+
+        for i in 0..5 {
+            tree.insert(format!("leaf{i}")).unwrap();
+            imt.insert(format!("leaf{i}")).unwrap();
+        }
+
+        tree.insert_many(vec!["a".to_string(), "b".to_string()])
+            .unwrap();
+        imt.insert_many(vec!["a".to_string(), "b".to_string()])
+            .unwrap();
+        tree.update(1, "new_leaf".to_string()).unwrap();
+        imt.update(1, "new_leaf".to_string()).unwrap();
+        tree.delete(3).unwrap();
+        imt.delete(3).unwrap();
+
+        assert_eq!(tree.root(), imt.root());
+        assert_eq!(tree.try_nodes().unwrap(), imt.nodes());
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_reopen_file_store() {
+        let path = store_path("reopen");
+        let mut tree = file_tree(&path);
+
+        for i in 0..5 {
+            tree.insert(format!("leaf{i}")).unwrap();
+        }
+        tree.update(2, "new_leaf".to_string()).unwrap();
+
+        let mut reopened = file_tree(&path);
+
+        assert_eq!(reopened.root(), tree.root());
+        assert_eq!(reopened.try_nodes().unwrap(), tree.try_nodes().unwrap());
+
+        let proof = reopened.create_proof(2).unwrap();
+        assert!(reopened.verify_proof(&proof));
+
+        reopened.insert("leaf5".to_string()).unwrap();
+        tree.insert("leaf5".to_string()).unwrap();
+        assert_eq!(reopened.root(), tree.root());
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn should_discard_incomplete_batch() {
+        let path = store_path("incomplete");
+        let mut tree = file_tree(&path);
+
+        for i in 0..3 {
+            tree.insert(format!("leaf{i}")).unwrap();
+        }
+
+        let len = fs::metadata(path.join(LOG_FILE)).unwrap().len();
+
+        // A crash in the middle of a commit leaves a partial batch at the end of the log.
+        append_to_log(&path, &[42, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3]);
+
+        let mut reopened = file_tree(&path);
+
+        assert_eq!(reopened.root(), tree.root());
+        assert_eq!(fs::metadata(path.join(LOG_FILE)).unwrap().len(), len);
+
+        reopened.insert("leaf3".to_string()).unwrap();
+        tree.insert("leaf3".to_string()).unwrap();
+        assert_eq!(file_tree(&path).root(), tree.root());
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_rollback_file_store() {
+        let path = store_path("rollback");
+        let mut tree = file_tree(&path);
+
+        for i in 0..3 {
+            tree.insert(format!("leaf{i}")).unwrap();
+        }
+
+        let nodes = tree.try_nodes().unwrap();
+        let checkpoint = tree.checkpoint();
+
+        tree.insert("leaf3".to_string()).unwrap();
+        tree.update(1, "new_leaf".to_string()).unwrap();
+        tree.rollback(checkpoint).unwrap();
+
+        assert_eq!(tree.try_nodes().unwrap(), nodes);
+        assert_eq!(file_tree(&path).try_nodes().unwrap(), nodes);
+
+        tree.insert("leaf4".to_string()).unwrap();
+        assert_eq!(
+            file_tree(&path).try_nodes().unwrap(),
+            tree.try_nodes().unwrap()
+        );
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_apply_batch_again_after_crash() {
+        let path = store_path("apply-again");
+        let mut tree = file_tree(&path);
+
+        for i in 0..3 {
+            tree.insert(format!("leaf{i}")).unwrap();
+        }
+
+        // A crash after the last batch is written to the log, before it is marked as applied.
+        let applied = fs::read(path.join(APPLIED_FILE)).unwrap();
+        tree.insert("leaf3".to_string()).unwrap();
+        tree.update(0, "new_leaf".to_string()).unwrap();
+        fs::write(path.join(APPLIED_FILE), applied).unwrap();
+
+        let reopened = file_tree(&path);
+
+        assert_eq!(reopened.root(), tree.root());
+        assert_eq!(reopened.try_nodes().unwrap(), tree.try_nodes().unwrap());
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn should_not_open_corrupted_batch() {
+        let path = store_path("corrupted-batch");
+        let mut tree = file_tree(&path);
+
+        tree.insert("leaf0".to_string()).unwrap();
+        let applied = fs::read(path.join(APPLIED_FILE)).unwrap();
+        tree.insert("leaf1".to_string()).unwrap();
+        tree.insert("leaf2".to_string()).unwrap();
+        fs::write(path.join(APPLIED_FILE), applied).unwrap();
+
+        // The batch of `leaf1` is not applied yet and is followed by another one, so it cannot
+        // have been left by a crash.
+        let applied_len = u64::from_le_bytes(
+            fs::read(path.join(APPLIED_FILE))
+                .unwrap()
+                .try_into()
+                .unwrap(),
+        );
+        let mut log = fs::read(path.join(LOG_FILE)).unwrap();
+        log[applied_len as usize + 8] ^= 1;
+        fs::write(path.join(LOG_FILE), log).unwrap();
+
+        assert_eq!(
+            FileStore::<String>::open(&path).err(),
+            Some(IMTError::CorruptedData("checksum mismatch of a log batch"))
+        );
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn should_not_read_corrupted_node() {
+        let path = store_path("corrupted-node");
+        let mut tree = file_tree(&path);
+
+        tree.insert("leaf0".to_string()).unwrap();
+        tree.insert("leaf1".to_string()).unwrap();
+
+        // Replaces the last byte of `leaf1` in the log with an invalid UTF-8 byte.
+        let mut log = fs::read(path.join(LOG_FILE)).unwrap();
+        let node = [&5u32.to_le_bytes()[..], b"leaf1"].concat();
+        let position = log.windows(9).position(|bytes| bytes == node).unwrap();
+        log[position + 8] = 0xff;
+        fs::write(path.join(LOG_FILE), log).unwrap();
+
+        let reopened = file_tree(&path);
+
+        assert_eq!(
+            reopened.create_proof(1).err(),
+            Some(IMTError::CorruptedData("invalid node encoding"))
+        );
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn should_poison_store_after_failed_commit() {
+        let path = store_path("poisoned");
+        let mut store = FileStore::<String>::open(&path).unwrap();
+
+        store.set(0, 0, "a".to_string());
+        store.commit().unwrap();
+
+        // The batch of `b` is in the log, but it is not applied to the index files.
+        store.fail_before_apply = true;
+        store.set(0, 1, "b".to_string());
+        assert_eq!(
+            store.commit().err(),
+            Some(IMTError::Io(io::ErrorKind::Other))
+        );
+
+        store.fail_before_apply = false;
+        assert_eq!(store.commit().err(), Some(IMTError::StorePoisoned));
+        assert_eq!(store.get(0, 0).err(), Some(IMTError::StorePoisoned));
+        drop(store);
+
+        let mut store = FileStore::<String>::open(&path).unwrap();
+        assert_eq!(store.get(0, 1).unwrap(), Some("b".to_string()));
+
+        store.set(0, 2, "c".to_string());
+        store.commit().unwrap();
+        drop(store);
+
+        let store = FileStore::<String>::open(&path).unwrap();
+        let leaves: Vec<_> = (0..3).map(|index| store.get(0, index).unwrap()).collect();
+        assert_eq!(
+            leaves,
+            [
+                Some("a".to_string()),
+                Some("b".to_string()),
+                Some("c".to_string())
+            ]
+        );
+
+        fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt::Display;
+
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+
+use crate::imt::{IMTError, IMTMerkleProof, IMTNode};
+
+/// Shape of the path of a proof in a circuit witness.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WitnessLayout {
+    /// `leaf`, `depth`, `index` and `siblings[max_depth]` of a binary tree, where `index` packs
+    /// the path bits and `depth` is the actual depth of the proof, as taken by zk-kit's
+    /// `BinaryMerkleRoot` circom template and `binary_merkle_root` Noir function.
+    BinaryPacked,
+    /// `leaf`, `path_indices[max_depth]` as bits and `siblings[max_depth]` of a binary tree.
+    BinaryBits,
+    /// `leaf`, `path_indices[max_depth]` between 0 and `arity - 1` and
+    /// `siblings[max_depth][arity - 1]`, for trees of any arity.
+    KAry,
+}
+
+/// A node that can be written as a value of a witness.
+pub trait WitnessValue {
+    fn to_witness_value(&self) -> String;
+}
+
+impl WitnessValue for String {
+    fn to_witness_value(&self) -> String {
+        self.clone()
+    }
+}
+
+/// Field elements are written in decimal. Zero is written as `0`, whereas `Fr`'s `Display`
+/// writes it as an empty string.
+impl WitnessValue for Fr {
+    fn to_witness_value(&self) -> String {
+        self.into_bigint().to_string()
+    }
+}
+
+/// Words are written as `0x`-prefixed hexadecimal, which circom and Noir parse as numbers.
+impl WitnessValue for [u8; 32] {
+    fn to_witness_value(&self) -> String {
+        format!("0x{}", hex::encode(self))
+    }
+}
+
+/// Inputs of a Merkle proof circuit, padded to the maximum depth of the circuit.
+///
+/// Levels after the depth of the proof have padding siblings and a path index of 0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IMTWitness<N = IMTNode> {
+    layout: WitnessLayout,
+    leaf: N,
+    depth: usize,
+    index: usize,
+    path_indices: Vec<usize>,
+    siblings: Vec<Vec<N>>,
+}
+
+impl<N: Clone> IMTMerkleProof<N> {
+    /// Creates the witness of the proof for a circuit with the given layout and maximum depth.
+    ///
+    /// The binary layouts require a binary tree, and the depth of the proof cannot be greater
+    /// than `max_depth`.
+    pub fn to_witness(
+        &self,
+        layout: WitnessLayout,
+        max_depth: usize,
+        padding: N,
+    ) -> Result<IMTWitness<N>, IMTError> {
+        let depth = self.siblings().len();
+        let siblings_per_level = self.siblings().first().map_or(1, Vec::len);
+
+        if layout != WitnessLayout::KAry && siblings_per_level != 1 {
+            return Err(IMTError::InvalidArity);
+        }
+
+        if depth > max_depth {
+            return Err(IMTError::InvalidDepth);
+        }
+
+        let arity = siblings_per_level + 1;
+        let mut index = 0usize;
+
+        for &path_index in self.path_indices().iter().rev() {
+            index = index
+                .checked_mul(arity)
+                .and_then(|index| index.checked_add(path_index))
+                .ok_or(IMTError::InvalidDepth)?;
+        }
+
+        let mut path_indices = self.path_indices().to_vec();
+        let mut siblings = self.siblings().to_vec();
+
+        path_indices.resize(max_depth, 0);
+        siblings.resize(max_depth, vec![padding; siblings_per_level]);
+
+        Ok(IMTWitness {
+            layout,
+            leaf: self.leaf().clone(),
+            depth,
+            index,
+            path_indices,
+            siblings,
+        })
+    }
+}
+
+impl<N> IMTWitness<N> {
+    pub fn layout(&self) -> WitnessLayout {
+        self.layout
+    }
+
+    pub fn leaf(&self) -> &N {
+        &self.leaf
+    }
+
+    /// The depth of the proof, before padding.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The index of the leaf, i.e. the path indices packed in base `arity`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn path_indices(&self) -> &[usize] {
+        &self.path_indices
+    }
+
+    pub fn siblings(&self) -> &[Vec<N>] {
+        &self.siblings
+    }
+}
+
+impl<N: WitnessValue> IMTWitness<N> {
+    /// Returns the witness as a circom `input.json` object, with `pathIndices` for the path.
+    ///
+    /// Every value is a string, as circom expects for field elements.
+    pub fn to_circom_input(&self) -> String {
+        let fields = self
+            .fields("pathIndices")
+            .into_iter()
+            .map(|(name, value)| format!("\"{name}\":{value}"))
+            .collect::<Vec<_>>();
+
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// Returns the witness as a Noir `Prover.toml` file, with `path_indices` for the path.
+    pub fn to_noir_prover_toml(&self) -> String {
+        self.fields("path_indices")
+            .into_iter()
+            .map(|(name, value)| format!("{name} = {value}\n"))
+            .collect()
+    }
+
+    /// Returns the names and the values of the inputs of the layout. Values are written with
+    /// the syntax shared by JSON and TOML.
+    fn fields(&self, path_indices_name: &'static str) -> Vec<(&'static str, String)> {
+        let leaf = ("leaf", quote(self.leaf.to_witness_value()));
+
+        match self.layout {
+            WitnessLayout::BinaryPacked => vec![
+                leaf,
+                ("depth", quote(self.depth)),
+                ("index", quote(self.index)),
+                (
+                    "siblings",
+                    array(
+                        self.siblings
+                            .iter()
+                            .map(|level| quote(level[0].to_witness_value())),
+                    ),
+                ),
+            ],
+            WitnessLayout::BinaryBits => vec![
+                leaf,
+                (
+                    path_indices_name,
+                    array(self.path_indices.iter().map(quote)),
+                ),
+                (
+                    "siblings",
+                    array(
+                        self.siblings
+                            .iter()
+                            .map(|level| quote(level[0].to_witness_value())),
+                    ),
+                ),
+            ],
+            WitnessLayout::KAry => vec![
+                leaf,
+                (
+                    path_indices_name,
+                    array(self.path_indices.iter().map(quote)),
+                ),
+                (
+                    "siblings",
+                    array(self.siblings.iter().map(|level| {
+                        array(level.iter().map(|node| quote(node.to_witness_value())))
+                    })),
+                ),
+            ],
+        }
+    }
+}
+
+/// Writes a value as a string, escaping quotes and backslashes.
+fn quote<T: Display>(value: T) -> String {
+    let value = value.to_string();
+    let mut quoted = String::with_capacity(value.len() + 2);
+
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+fn array(values: impl Iterator<Item = String>) -> String {
+    format!("[{}]", values.collect::<Vec<_>>().join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{Hasher, Keccak256Hasher, PoseidonHasher};
+    use crate::imt::{IMTHashFunction, IMT};
+    use ark_ff::Zero;
+
+    fn hash_function(nodes: Vec<String>) -> String {
+        nodes.join("-")
+    }
+
+    fn proof(arity: usize, index: usize) -> IMTMerkleProof {
+        let hash: IMTHashFunction = hash_function;
+        let leaves = (1..=4).map(|i| i.to_string()).collect();
+        let imt = IMT::new(hash, 2, "0".to_string(), arity, leaves).unwrap();
+
+        imt.create_proof(index).unwrap()
+    }
+
+    #[test]
+    fn test_binary_packed_witness() {
+        let witness = proof(2, 2)
+            .to_witness(WitnessLayout::BinaryPacked, 4, "0".to_string())
+            .unwrap();
+
+        assert_eq!(witness.depth(), 2);
+        assert_eq!(witness.index(), 2);
+        assert_eq!(witness.path_indices(), [0, 1, 0, 0]);
+        assert_eq!(
+            witness.to_circom_input(),
+            r#"{"leaf":"3","depth":"2","index":"2","siblings":["4","1-2","0","0"]}"#
+        );
+        assert_eq!(
+            witness.to_noir_prover_toml(),
+            "leaf = \"3\"\ndepth = \"2\"\nindex = \"2\"\nsiblings = [\"4\",\"1-2\",\"0\",\"0\"]\n"
+        );
+    }
+
+    #[test]
+    fn test_binary_bits_witness() {
+        let witness = proof(2, 1)
+            .to_witness(WitnessLayout::BinaryBits, 3, "0".to_string())
+            .unwrap();
+
+        assert_eq!(
+            witness.to_circom_input(),
+            r#"{"leaf":"2","pathIndices":["1","0","0"],"siblings":["1","3-4","0"]}"#
+        );
+        assert_eq!(
+            witness.to_noir_prover_toml(),
+            "leaf = \"2\"\npath_indices = [\"1\",\"0\",\"0\"]\nsiblings = [\"1\",\"3-4\",\"0\"]\n"
+        );
+    }
+
+    #[test]
+    fn test_k_ary_witness() {
+        let witness = proof(3, 3)
+            .to_witness(WitnessLayout::KAry, 3, "0".to_string())
+            .unwrap();
+
+        assert_eq!(witness.index(), 3);
+        assert_eq!(
+            witness.to_circom_input(),
+            r#"{"leaf":"4","pathIndices":["0","1","0"],"siblings":[["0","0"],["1-2-3","0-0-0"],["0","0"]]}"#
+        );
+    }
+
+    #[test]
+    fn test_field_witness() {
+        let leaves: Vec<Fr> = (1..=3u64).map(Fr::from).collect();
+        let imt = IMT::new(PoseidonHasher::new(2).unwrap(), 2, Fr::zero(), 2, leaves).unwrap();
+        let witness = imt
+            .create_proof(0)
+            .unwrap()
+            .to_witness(WitnessLayout::BinaryPacked, 3, Fr::zero())
+            .unwrap();
+
+        assert_eq!(
+            witness.to_circom_input(),
+            format!(
+                r#"{{"leaf":"1","depth":"2","index":"0","siblings":["2","{}","0"]}}"#,
+                PoseidonHasher::new(2)
+                    .unwrap()
+                    .hash(vec![Fr::from(3u64), Fr::zero()])
+            )
+        );
+    }
+
+    #[test]
+    fn test_bytes_witness() {
+        let imt = IMT::new(Keccak256Hasher, 1, [0u8; 32], 2, vec![[1u8; 32]]).unwrap();
+        let witness = imt
+            .create_proof(0)
+            .unwrap()
+            .to_witness(WitnessLayout::BinaryBits, 1, [0u8; 32])
+            .unwrap();
+
+        assert_eq!(
+            witness.to_noir_prover_toml(),
+            format!(
+                "leaf = \"0x{}\"\npath_indices = [\"0\"]\nsiblings = [\"0x{}\"]\n",
+                "01".repeat(32),
+                "00".repeat(32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_witness_escapes_strings() {
+        let proof = IMTMerkleProof::new(
+            "root".to_string(),
+            "a\"b".to_string(),
+            vec![0],
+            vec![vec!["c\\d".to_string()]],
+        )
+        .unwrap();
+        let witness = proof
+            .to_witness(WitnessLayout::BinaryBits, 1, "0".to_string())
+            .unwrap();
+
+        assert_eq!(
+            witness.to_circom_input(),
+            r#"{"leaf":"a\"b","pathIndices":["0"],"siblings":["c\\d"]}"#
+        );
+    }
+
+    #[test]
+    fn should_not_create_invalid_witness() {
+        assert_eq!(
+            proof(3, 0)
+                .to_witness(WitnessLayout::BinaryBits, 4, "0".to_string())
+                .err(),
+            Some(IMTError::InvalidArity)
+        );
+        assert_eq!(
+            proof(2, 0)
+                .to_witness(WitnessLayout::BinaryPacked, 1, "0".to_string())
+                .err(),
+            Some(IMTError::InvalidDepth)
+        );
+    }
+}
@@ -26,8 +26,8 @@ fn main() {
     assert_eq!(smt.get(key.clone()), Some(new_value));
 
     // Create and verify a proof for the key.
-    let create_proof = smt.create_proof(key.clone());
-    let verify_proof = smt.verify_proof(create_proof);
+    let create_proof = smt.create_proof(key.clone()).unwrap();
+    let verify_proof = smt.verify_proof(&create_proof);
     assert!(verify_proof);
 
     // Delete the key.
@@ -0,0 +1,231 @@
+use ark_bn254::Fr;
+use ark_ff::{PrimeField, Zero};
+
+use crate::smt::{MerkleProof, SMTError};
+
+/// Inputs of circomlib's `SMTVerifier(nLevels)` template for a proof, without `enabled`, which
+/// is usually set by the enclosing circuit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SMTVerifierInputs {
+    /// 0 to verify an inclusion, 1 to verify an exclusion.
+    pub fnc: u8,
+    pub root: Fr,
+    /// The siblings of the proof, padded with zeros to `nLevels`.
+    pub siblings: Vec<Fr>,
+    /// For exclusions, the entry found at the position of the key, if any.
+    pub old_key: Fr,
+    pub old_value: Fr,
+    /// For exclusions, whether the position of the key is empty.
+    pub is_old0: bool,
+    pub key: Fr,
+    /// The value of the key for inclusions, 0 for exclusions.
+    pub value: Fr,
+}
+
+impl MerkleProof<Fr> {
+    /// Creates the `SMTVerifier` inputs of a membership or non-membership proof.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_levels` - The number of levels of the circuit.
+    ///
+    /// # Returns
+    ///
+    /// The inputs, or an error if the proof has `n_levels` siblings or more, since the last
+    /// sibling of the circuit must be zero.
+    pub fn to_smt_verifier_inputs(&self, n_levels: usize) -> Result<SMTVerifierInputs, SMTError> {
+        let siblings = self.siblings();
+
+        if siblings.len() >= n_levels {
+            return Err(SMTError::TooManySiblings(siblings.len(), n_levels));
+        }
+
+        let mut padded_siblings = siblings.to_vec();
+        padded_siblings.resize(n_levels, Fr::zero());
+
+        let entry = self.entry();
+        let mut inputs = SMTVerifierInputs {
+            fnc: 0,
+            root: *self.root(),
+            siblings: padded_siblings,
+            old_key: Fr::zero(),
+            old_value: Fr::zero(),
+            is_old0: false,
+            key: entry[0],
+            value: Fr::zero(),
+        };
+
+        if let Some(value) = entry.get(1) {
+            inputs.value = *value;
+        } else {
+            inputs.fnc = 1;
+
+            match self.matching_entry() {
+                Some(matching_entry) => {
+                    inputs.old_key = matching_entry[0];
+                    inputs.old_value = matching_entry[1];
+                },
+                None => inputs.is_old0 = true,
+            }
+        }
+
+        Ok(inputs)
+    }
+}
+
+impl SMTVerifierInputs {
+    /// Returns the inputs as a circom `input.json` object, with every value as a decimal string.
+    pub fn to_circom_input(&self) -> String {
+        let siblings = self
+            .siblings
+            .iter()
+            .map(|sibling| format!("\"{}\"", decimal(sibling)))
+            .collect::<Vec<_>>();
+
+        format!(
+            "{{\"fnc\":\"{}\",\"root\":\"{}\",\"siblings\":[{}],\"oldKey\":\"{}\",\"oldValue\":\"{}\",\"isOld0\":\"{}\",\"key\":\"{}\",\"value\":\"{}\"}}",
+            self.fnc,
+            decimal(&self.root),
+            siblings.join(","),
+            decimal(&self.old_key),
+            decimal(&self.old_value),
+            u8::from(self.is_old0),
+            decimal(&self.key),
+            decimal(&self.value)
+        )
+    }
+}
+
+/// Writes a field element in decimal, including zero, which `Fr`'s `Display` writes as an
+/// empty string.
+fn decimal(value: &Fr) -> String {
+    value.into_bigint().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon::{PoseidonSMT, PoseidonSmtHasher};
+    use crate::smt::SmtHasher;
+
+    /// Computes the root as `SMTVerifier` does, from the leaf at the level of the last non-zero
+    /// sibling.
+    fn circuit_root(inputs: &SMTVerifierInputs) -> Fr {
+        let hasher = PoseidonSmtHasher::new();
+        let levels = inputs
+            .siblings
+            .iter()
+            .rposition(|sibling| !sibling.is_zero())
+            .map_or(0, |i| i + 1);
+        let path = crate::smt::SmtNode::to_path(&inputs.key, crate::smt::MAX_DEPTH).unwrap();
+
+        let mut node = if inputs.fnc == 0 {
+            hasher.hash(vec![inputs.key, inputs.value, Fr::from(1u64)])
+        } else if inputs.is_old0 {
+            Fr::zero()
+        } else {
+            hasher.hash(vec![inputs.old_key, inputs.old_value, Fr::from(1u64)])
+        };
+
+        for level in (0..levels).rev() {
+            let sibling = inputs.siblings[level];
+            node = if path[level] == 1 {
+                hasher.hash(vec![sibling, node])
+            } else {
+                hasher.hash(vec![node, sibling])
+            };
+        }
+
+        node
+    }
+
+    fn smt() -> PoseidonSMT {
+        let mut smt = PoseidonSMT::poseidon();
+
+        for key in [1u64, 3, 8, 24, 5] {
+            smt.add(Fr::from(key), Fr::from(key * 10)).unwrap();
+        }
+
+        smt
+    }
+
+    #[test]
+    fn test_inclusion_inputs() {
+        let smt = smt();
+
+        for key in [1u64, 3, 8, 24, 5] {
+            let inputs = smt
+                .create_proof(Fr::from(key))
+                .unwrap()
+                .to_smt_verifier_inputs(10)
+                .unwrap();
+
+            assert_eq!(inputs.fnc, 0);
+            assert_eq!(inputs.value, Fr::from(key * 10));
+            assert_eq!(inputs.siblings.len(), 10);
+            assert_eq!(circuit_root(&inputs), smt.root());
+        }
+    }
+
+    #[test]
+    fn test_exclusion_inputs() {
+        let smt = smt();
+
+        // 7 shares its first bits with 3, and 2 leads to an empty subtree next to 8 and 24.
+        let inputs = smt
+            .create_proof(Fr::from(7u64))
+            .unwrap()
+            .to_smt_verifier_inputs(10)
+            .unwrap();
+        assert_eq!(inputs.fnc, 1);
+        assert!(!inputs.is_old0);
+        assert_eq!(inputs.old_key, Fr::from(3u64));
+        assert_eq!(inputs.old_value, Fr::from(30u64));
+        assert_eq!(circuit_root(&inputs), smt.root());
+
+        let inputs = smt
+            .create_proof(Fr::from(2u64))
+            .unwrap()
+            .to_smt_verifier_inputs(10)
+            .unwrap();
+        assert_eq!(inputs.fnc, 1);
+        assert!(inputs.is_old0);
+        assert_eq!(inputs.value, Fr::zero());
+        assert_eq!(circuit_root(&inputs), smt.root());
+    }
+
+    #[test]
+    fn test_circom_input() {
+        let mut smt = PoseidonSMT::poseidon();
+        smt.add(Fr::from(1u64), Fr::from(10u64)).unwrap();
+        smt.add(Fr::from(2u64), Fr::from(20u64)).unwrap();
+
+        let inputs = smt
+            .create_proof(Fr::from(1u64))
+            .unwrap()
+            .to_smt_verifier_inputs(3)
+            .unwrap();
+
+        assert_eq!(
+            inputs.to_circom_input(),
+            format!(
+                r#"{{"fnc":"0","root":"{}","siblings":["{}","0","0"],"oldKey":"0","oldValue":"0","isOld0":"0","key":"1","value":"10"}}"#,
+                smt.root(),
+                inputs.siblings[0]
+            )
+        );
+    }
+
+    #[test]
+    fn should_not_export_too_many_siblings() {
+        let smt = smt();
+        let proof = smt.create_proof(Fr::from(8u64)).unwrap();
+        let siblings = proof.siblings().len();
+
+        assert_eq!(
+            proof.to_smt_verifier_inputs(siblings),
+            Err(SMTError::TooManySiblings(siblings, siblings))
+        );
+        assert!(proof.to_smt_verifier_inputs(siblings + 1).is_ok());
+    }
+}
@@ -1,2 +1,5 @@
+pub mod circom;
+pub mod poseidon;
 pub mod smt;
+pub mod transition;
 mod utils;
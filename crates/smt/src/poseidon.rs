@@ -0,0 +1,161 @@
+use ark_bn254::Fr;
+use zk_kit_imt::hash::{Hasher, PoseidonHasher};
+
+use crate::smt::{SmtHasher, SMT};
+
+/// Poseidon hasher compatible with the sparse Merkle trees of circomlib and circomlibjs.
+///
+/// Leaves are hashed as `Poseidon(key, value, 1)` and inner nodes as `Poseidon(left, right)`,
+/// as circomlib's `SMTVerifier` and `SMTProcessor` templates do.
+#[derive(Clone, Debug)]
+pub struct PoseidonSmtHasher {
+    inner: PoseidonHasher,
+    leaf: PoseidonHasher,
+}
+
+/// A sparse Merkle tree whose roots and proofs can be checked by circomlib's circuits.
+pub type PoseidonSMT = SMT<PoseidonSmtHasher>;
+
+impl PoseidonSmtHasher {
+    pub fn new() -> Self {
+        PoseidonSmtHasher {
+            inner: PoseidonHasher::new(2).expect("2 is a valid Poseidon arity"),
+            leaf: PoseidonHasher::new(3).expect("3 is a valid Poseidon arity"),
+        }
+    }
+}
+
+impl Default for PoseidonSmtHasher {
+    fn default() -> Self {
+        PoseidonSmtHasher::new()
+    }
+}
+
+impl SmtHasher for PoseidonSmtHasher {
+    type Node = Fr;
+
+    /// Hashes `[left, right]` into an inner node or `[key, value, 1]` into a leaf.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are not 2 or 3 nodes.
+    fn hash(&self, nodes: Vec<Fr>) -> Fr {
+        match nodes.len() {
+            2 => self.inner.hash(nodes),
+            3 => self.leaf.hash(nodes),
+            len => panic!("Cannot hash {len} nodes, only inner nodes and leaves"),
+        }
+    }
+}
+
+impl PoseidonSMT {
+    /// Initializes an empty tree with the Poseidon hasher, 0 as zero node and 1 as entry mark.
+    pub fn poseidon() -> Self {
+        SMT::with_hasher(PoseidonSmtHasher::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn fr(value: &str) -> Fr {
+        Fr::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn test_poseidon_hashes() {
+        let hasher = PoseidonSmtHasher::new();
+
+        // circomlibjs `poseidon([1, 2])`.
+        assert_eq!(
+            hasher.hash(vec![Fr::from(1u64), Fr::from(2u64)]),
+            fr("7853200120776062878684798364095072458815029376092732009249414926327459813530")
+        );
+        assert_eq!(
+            hasher.hash(vec![Fr::from(1u64), Fr::from(2u64), Fr::from(1u64)]),
+            PoseidonHasher::new(3).unwrap().hash(vec![
+                Fr::from(1u64),
+                Fr::from(2u64),
+                Fr::from(1u64)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_poseidon_smt_roots() {
+        let hasher = PoseidonSmtHasher::new();
+        let leaf = |key: u64, value: u64| {
+            hasher.hash(vec![Fr::from(key), Fr::from(value), Fr::from(1u64)])
+        };
+        let mut smt = PoseidonSMT::poseidon();
+
+        // A single leaf is the root, as in circomlibjs.
+        smt.add(Fr::from(1u64), Fr::from(10u64)).unwrap();
+        assert_eq!(smt.root(), leaf(1, 10));
+
+        // The keys differ in their first bit, and the even key goes on the left.
+        smt.add(Fr::from(2u64), Fr::from(20u64)).unwrap();
+        assert_eq!(smt.root(), hasher.hash(vec![leaf(2, 20), leaf(1, 10)]));
+
+        // The keys 1 and 3 share their first bit, so an empty subtree is on the left.
+        smt.delete(Fr::from(2u64)).unwrap();
+        smt.add(Fr::from(3u64), Fr::from(30u64)).unwrap();
+        assert_eq!(
+            smt.root(),
+            hasher.hash(vec![
+                Fr::from(0u64),
+                hasher.hash(vec![leaf(1, 10), leaf(3, 30)])
+            ])
+        );
+    }
+
+    #[test]
+    fn test_circomlibjs_roots() {
+        let mut smt = PoseidonSMT::poseidon();
+
+        // Roots of circomlibjs' `newMemEmptyTrie` after each of `insert(111, 222)`,
+        // `insert(333, 444)` and `insert(777, 888)`, then `update(333, 555)` and `delete(333)`.
+        let insertions = [
+            (
+                111u64,
+                222u64,
+                "9308772482099879945566979599408036177864352098141198065063141880905857869998",
+            ),
+            (
+                333,
+                444,
+                "1288560299535560961253537358119722684041344245980076865013927406034193350532",
+            ),
+            (
+                777,
+                888,
+                "101131104520982246402808792930595997860452583822086942239621952771844415143",
+            ),
+        ];
+
+        for (key, value, root) in insertions {
+            smt.add(Fr::from(key), Fr::from(value)).unwrap();
+            assert_eq!(smt.root(), fr(root));
+        }
+
+        smt.update(Fr::from(333u64), Fr::from(555u64)).unwrap();
+        assert_eq!(
+            smt.root(),
+            fr("5975728091840802576720326558945159610079342367204428329048132174353056287164")
+        );
+
+        smt.delete(Fr::from(333u64)).unwrap();
+        assert_eq!(
+            smt.root(),
+            fr("18678024693162528436295652764247600207306684124640649030657096469299295524121")
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_poseidon_invalid_number_of_nodes() {
+        PoseidonSmtHasher::new().hash(vec![Fr::from(1u64)]);
+    }
+}
@@ -1,6 +1,8 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, hash::Hash, str::FromStr};
 
-use num_bigint::BigInt;
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use num_bigint::{BigInt, Sign};
 
 use crate::utils::{
     get_first_common_elements, get_index_of_last_non_zero_element, is_hexadecimal, key_to_path,
@@ -8,12 +10,20 @@ use crate::utils::{
 
 use std::fmt;
 
+/// The maximum depth of a tree, i.e. the number of bits of the longest keys.
+pub const MAX_DEPTH: usize = 256;
+
 #[derive(Debug, PartialEq)]
 pub enum SMTError {
     KeyAlreadyExist(String),
     KeyDoesNotExist(String),
     InvalidParameterType(String, String),
     InvalidSiblingIndex,
+    TooManySiblings(usize, usize),
+    MalformedProof(&'static str),
+    InvalidDepth(usize),
+    KeyOutOfRange(String, usize),
+    DepthExhausted(usize),
 }
 
 impl fmt::Display for SMTError {
@@ -25,12 +35,64 @@ impl fmt::Display for SMTError {
                 write!(f, "Parameter {} must be a {}", p, t)
             },
             SMTError::InvalidSiblingIndex => write!(f, "Invalid sibling index"),
+            SMTError::TooManySiblings(s, l) => {
+                write!(
+                    f,
+                    "A proof with {} siblings does not fit in {} levels",
+                    s, l
+                )
+            },
+            SMTError::MalformedProof(reason) => write!(f, "Malformed proof: {}", reason),
+            SMTError::InvalidDepth(d) => {
+                write!(f, "Depth {} must be between 1 and {}", d, MAX_DEPTH)
+            },
+            SMTError::KeyOutOfRange(k, d) => write!(f, "Key {} does not fit in {} bits", k, d),
+            SMTError::DepthExhausted(d) => {
+                write!(f, "Two keys have the same path in {} levels", d)
+            },
         }
     }
 }
 
 impl std::error::Error for SMTError {}
 
+/// A node of the tree, which is also the type of its keys and values.
+///
+/// Keys are mapped to paths by their bits, so fixed-size nodes such as `[u8; 32]` or field
+/// elements are used as they are, without any string conversion.
+pub trait SmtNode: Clone + Eq + Hash + fmt::Debug {
+    /// The node of empty subtrees.
+    fn zero() -> Self;
+
+    /// The third element of the leaf entries, which tells leaves and inner nodes apart.
+    fn one() -> Self;
+
+    /// Returns the key as a big-endian unsigned integer, whose bits are its path.
+    fn to_key_bytes(&self) -> Result<Vec<u8>, SMTError>;
+
+    /// Returns the first `depth` bits of the key used as its path, from the least significant
+    /// bit.
+    ///
+    /// # Returns
+    ///
+    /// The path, or an error if the key is not a valid key or does not fit in `depth` bits.
+    fn to_path(&self, depth: usize) -> Result<Vec<usize>, SMTError> {
+        key_to_path(&self.to_key_bytes()?, depth)
+            .ok_or_else(|| SMTError::KeyOutOfRange(self.to_node_string(), depth))
+    }
+
+    /// Returns the node as text, e.g. in error messages.
+    fn to_node_string(&self) -> String;
+}
+
+/// A hash function used to compute the leaf nodes, from `[key, value, one]`, and the inner
+/// nodes, from `[left, right]`.
+pub trait SmtHasher {
+    type Node: SmtNode;
+
+    fn hash(&self, nodes: Vec<Self::Node>) -> Self::Node;
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Node {
     Str(String),
@@ -63,6 +125,86 @@ impl FromStr for Node {
     }
 }
 
+/// String and BigInt nodes of the original API.
+///
+/// BigInt keys are mapped to paths by their value and string keys are always read as
+/// hexadecimal numbers, as in the JS library, so `Str("300")` and `BigInt(300)` are different
+/// keys. Since different nodes can have the same number, e.g. `Str("a")` and `BigInt(10)`,
+/// only one of them can be a key of a tree.
+impl SmtNode for Node {
+    fn zero() -> Self {
+        Node::BigInt(BigInt::from(0))
+    }
+
+    fn one() -> Self {
+        Node::BigInt(BigInt::from(1))
+    }
+
+    fn to_key_bytes(&self) -> Result<Vec<u8>, SMTError> {
+        let number = match self {
+            Node::BigInt(n) => Some(n.clone()),
+            Node::Str(s) => is_hexadecimal(s)
+                .then(|| BigInt::parse_bytes(s.as_bytes(), 16))
+                .flatten(),
+        };
+
+        match number {
+            Some(n) if n.sign() != Sign::Minus => Ok(n.to_bytes_be().1),
+            _ => Err(SMTError::InvalidParameterType(
+                self.to_string(),
+                "non-negative BigInt or hexadecimal string".to_string(),
+            )),
+        }
+    }
+
+    fn to_node_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// 256-bit big-endian words, e.g. Keccak-256 or SHA-256 digests.
+impl SmtNode for [u8; 32] {
+    fn zero() -> Self {
+        [0; 32]
+    }
+
+    fn one() -> Self {
+        let mut one = [0; 32];
+        one[31] = 1;
+
+        one
+    }
+
+    fn to_key_bytes(&self) -> Result<Vec<u8>, SMTError> {
+        Ok(self.to_vec())
+    }
+
+    fn to_node_string(&self) -> String {
+        self.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+/// Elements of the BN254 scalar field, as used by circom and Noir circuits.
+impl SmtNode for Fr {
+    fn zero() -> Self {
+        Fr::from(0u64)
+    }
+
+    fn one() -> Self {
+        Fr::from(1u64)
+    }
+
+    fn to_key_bytes(&self) -> Result<Vec<u8>, SMTError> {
+        Ok(self.into_bigint().to_bytes_be())
+    }
+
+    /// Writes the element in decimal. Unlike its `Display` implementation, zero is written as
+    /// `0` rather than as an empty string.
+    fn to_node_string(&self) -> String {
+        self.into_bigint().to_string()
+    }
+}
+
 pub type Key = Node;
 pub type Value = Node;
 pub type EntryMark = Node;
@@ -73,27 +215,156 @@ pub type Siblings = Vec<Node>;
 
 pub type HashFunction = fn(ChildNodes) -> Node;
 
-pub struct EntryResponse {
-    pub entry: Vec<Node>,
-    pub matching_entry: Option<Vec<Node>>,
-    pub siblings: Siblings,
+/// Any function over `Node` values can be used as a hasher, which keeps the original API
+/// working.
+impl<F> SmtHasher for F
+where
+    F: Fn(ChildNodes) -> Node,
+{
+    type Node = Node;
+
+    fn hash(&self, nodes: ChildNodes) -> Node {
+        self(nodes)
+    }
 }
 
-#[allow(dead_code)]
-pub struct MerkleProof {
-    entry_response: EntryResponse,
-    root: Node,
+pub struct EntryResponse<N = Node> {
+    pub entry: Vec<N>,
+    pub matching_entry: Option<Vec<N>>,
+    pub siblings: Vec<N>,
+}
+
+/// A membership or a non-membership proof of a key.
+///
+/// The entry is `[key, value, entry_mark]` for a membership proof and `[key]` otherwise. A
+/// non-membership proof can have the entry found at the position of the key, whose key
+/// shares the first bits of the key.
+pub struct MerkleProof<N = Node> {
+    entry_response: EntryResponse<N>,
+    root: N,
     membership: bool,
 }
 
-#[allow(dead_code)]
-pub struct SMT {
-    hash: HashFunction,
-    big_numbers: bool,
-    zero_node: Node,
-    entry_mark: Node,
-    nodes: HashMap<Node, Vec<Node>>,
-    root: Node,
+impl<N: SmtNode> MerkleProof<N> {
+    /// Creates a proof from its parts, checking that they are consistent.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The root of the tree.
+    /// * `entry` - The entry of the key, or only the key for a non-membership proof.
+    /// * `matching_entry` - The entry found at the position of a non-member key, if any.
+    /// * `siblings` - The siblings of the path, from the root.
+    /// * `entry_mark` - The entry mark of the tree, i.e. the third element of the leaves.
+    ///
+    /// # Returns
+    ///
+    /// The proof, or an error if the entries do not have 1 or 3 elements, if their third
+    /// element is not the entry mark, if a membership proof has a matching entry or if the
+    /// matching entry has the same key.
+    pub fn new(
+        root: N,
+        entry: Vec<N>,
+        matching_entry: Option<Vec<N>>,
+        siblings: Vec<N>,
+        entry_mark: &N,
+    ) -> Result<MerkleProof<N>, SMTError> {
+        let proof = MerkleProof {
+            membership: entry.len() == 3,
+            entry_response: EntryResponse {
+                entry,
+                matching_entry,
+                siblings,
+            },
+            root,
+        };
+
+        proof.validate(entry_mark)?;
+
+        Ok(proof)
+    }
+
+    pub fn root(&self) -> &N {
+        &self.root
+    }
+
+    pub fn key(&self) -> &N {
+        &self.entry_response.entry[0]
+    }
+
+    /// The value of the key, for a membership proof.
+    pub fn value(&self) -> Option<&N> {
+        self.entry_response.entry.get(1)
+    }
+
+    pub fn entry(&self) -> &[N] {
+        &self.entry_response.entry
+    }
+
+    pub fn matching_entry(&self) -> Option<&[N]> {
+        self.entry_response.matching_entry.as_deref()
+    }
+
+    pub fn siblings(&self) -> &[N] {
+        &self.entry_response.siblings
+    }
+
+    pub fn membership(&self) -> bool {
+        self.membership
+    }
+
+    fn validate(&self, entry_mark: &N) -> Result<(), SMTError> {
+        let EntryResponse {
+            entry,
+            matching_entry,
+            ..
+        } = &self.entry_response;
+
+        if entry.len() != 1 && entry.len() != 3 {
+            return Err(SMTError::MalformedProof(
+                "The entry must be a key or a key, a value and an entry mark",
+            ));
+        }
+
+        if self.membership != (entry.len() == 3) {
+            return Err(SMTError::MalformedProof(
+                "Only membership proofs must have a value",
+            ));
+        }
+
+        if self.membership && entry[2] != *entry_mark {
+            return Err(SMTError::MalformedProof(
+                "The entry must end with the entry mark",
+            ));
+        }
+
+        if let Some(matching_entry) = matching_entry {
+            if self.membership {
+                return Err(SMTError::MalformedProof(
+                    "A membership proof cannot have a matching entry",
+                ));
+            }
+
+            if matching_entry.len() != 3
+                || matching_entry[0] == entry[0]
+                || matching_entry[2] != *entry_mark
+            {
+                return Err(SMTError::MalformedProof(
+                    "The matching entry must be the entry of another key",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct SMT<H: SmtHasher = HashFunction> {
+    pub(crate) hash: H,
+    pub(crate) zero_node: H::Node,
+    pub(crate) entry_mark: H::Node,
+    pub(crate) nodes: HashMap<H::Node, Vec<H::Node>>,
+    root: H::Node,
+    depth: usize,
 }
 
 impl SMT {
@@ -108,27 +379,71 @@ impl SMT {
     ///
     /// A new instance of the SMT.
     pub fn new(hash: HashFunction, big_numbers: bool) -> Self {
-        let zero_node;
-        let entry_mark;
-
         if big_numbers {
-            zero_node = Node::BigInt(BigInt::from(0));
-            entry_mark = Node::BigInt(BigInt::from(1));
+            SMT::with_nodes(hash, Node::zero(), Node::one())
         } else {
-            zero_node = Node::Str("0".to_string());
-            entry_mark = Node::Str("1".to_string());
+            SMT::with_nodes(hash, Node::Str("0".to_string()), Node::Str("1".to_string()))
+        }
+    }
+}
+
+impl<H: SmtHasher> SMT<H> {
+    /// Initializes a new instance of the SMT whose nodes are the nodes of the hasher.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hasher used to hash the entries and the child nodes.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of the SMT, with `H::Node::zero()` as zero node and `H::Node::one()` as
+    /// entry mark.
+    pub fn with_hasher(hash: H) -> Self {
+        SMT::with_nodes(hash, H::Node::zero(), H::Node::one())
+    }
+
+    /// Initializes a new instance of the SMT whose keys fit in `depth` bits, e.g. 64, 160 or 254.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hasher used to hash the entries and the child nodes.
+    /// * `depth` - The maximum depth of the tree.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of the SMT, or an error if the depth is not between 1 and `MAX_DEPTH`.
+    pub fn with_depth(hash: H, depth: usize) -> Result<Self, SMTError> {
+        if depth == 0 || depth > MAX_DEPTH {
+            return Err(SMTError::InvalidDepth(depth));
         }
 
+        let mut smt = SMT::with_hasher(hash);
+        smt.depth = depth;
+
+        Ok(smt)
+    }
+
+    fn with_nodes(hash: H, zero_node: H::Node, entry_mark: H::Node) -> Self {
         SMT {
             hash,
-            big_numbers,
             zero_node: zero_node.clone(),
             entry_mark,
             nodes: HashMap::new(),
             root: zero_node,
+            depth: MAX_DEPTH,
         }
     }
 
+    /// Returns the root of the tree, which is the zero node when the tree is empty.
+    pub fn root(&self) -> H::Node {
+        self.root.clone()
+    }
+
+    /// Returns the maximum depth of the tree, i.e. the number of bits of the keys.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
     /// Retrieves the value associated with the given key from the SMT.
     ///
     /// # Arguments
@@ -138,10 +453,8 @@ impl SMT {
     /// # Returns
     ///
     /// An `Option` containing the value associated with the key, or `None` if the key does not exist.
-    pub fn get(&self, key: Key) -> Option<Value> {
-        let key = key.to_string().parse::<Node>().unwrap();
-
-        let EntryResponse { entry, .. } = self.retrieve_entry(key);
+    pub fn get(&self, key: H::Node) -> Option<H::Node> {
+        let EntryResponse { entry, .. } = self.retrieve_entry(key).ok()?;
 
         entry.get(1).cloned()
     }
@@ -159,30 +472,42 @@ impl SMT {
     /// # Returns
     ///
     /// An `Result` indicating whether the operation was successful or not.
-    pub fn add(&mut self, key: Key, value: Value) -> Result<(), SMTError> {
-        let key = key.to_string().parse::<Node>().unwrap();
-        let value = value.to_string().parse::<Node>().unwrap();
-
+    pub fn add(&mut self, key: H::Node, value: H::Node) -> Result<(), SMTError> {
         let EntryResponse {
             entry,
             matching_entry,
             mut siblings,
-        } = self.retrieve_entry(key.clone());
+        } = self.retrieve_entry(key.clone())?;
 
         if entry.get(1).is_some() {
-            return Err(SMTError::KeyAlreadyExist(key.to_string()));
+            return Err(SMTError::KeyAlreadyExist(key.to_node_string()));
         }
 
-        let path = key_to_path(&key.to_string());
+        let path = key.to_path(self.depth)?;
         // If there is a matching entry, its node is saved in the `node` variable, otherwise the
         // `zero_node` is saved. This node is used below as the first node (starting from the
         // bottom of the tree) to obtain the new nodes up to the root.
         let node = if let Some(ref matching_entry) = matching_entry {
-            (self.hash)(matching_entry.clone())
+            self.hash.hash(matching_entry.clone())
         } else {
             self.zero_node.clone()
         };
 
+        // If there is a matching entry, the new entry is added below the first bit where the paths of
+        // the two keys diverge. Keys with the same number have the same path and cannot diverge.
+        let first_matching_bits = if let Some(ref matching_entry) = matching_entry {
+            let matching_path = matching_entry[0].to_path(self.depth)?;
+            let first_matching_bits = get_first_common_elements(&path, &matching_path).len();
+
+            if first_matching_bits == self.depth {
+                return Err(SMTError::DepthExhausted(self.depth));
+            }
+
+            Some(first_matching_bits)
+        } else {
+            None
+        };
+
         // If there are siblings, the old nodes are deleted and will be re-created below with new hashes.
         if !siblings.is_empty() {
             self.delete_old_nodes(node.clone(), &path, &siblings)
@@ -191,21 +516,16 @@ impl SMT {
         // If there is a matching entry, further N zero siblings are added in the `siblings` vector,
         // followed by the matching node itself. N is the number of the first matching bits of the paths.
         // This is helpful in the non-membership proof verification as explained in the function below.
-        if let Some(matching_entry) = matching_entry {
-            let matching_path = key_to_path(&matching_entry[0].to_string());
-            let mut i = siblings.len();
-
-            while matching_path[i] == path[i] {
-                siblings.push(self.zero_node.clone());
-                i += 1;
-            }
-
+        if let Some(first_matching_bits) = first_matching_bits {
+            siblings.resize(first_matching_bits, self.zero_node.clone());
             siblings.push(node.clone());
         }
 
         // Adds the new entry and re-creates the nodes of the path with the new hashes with a bottom
         // up approach. The `add_new_nodes` function returns the new root of the tree.
-        let new_node = (self.hash)(vec![key.clone(), value.clone(), self.entry_mark.clone()]);
+        let new_node = self
+            .hash
+            .hash(vec![key.clone(), value.clone(), self.entry_mark.clone()]);
 
         self.nodes
             .insert(new_node.clone(), vec![key, value, self.entry_mark.clone()]);
@@ -229,26 +549,25 @@ impl SMT {
     /// # Returns
     ///
     /// An `Result` indicating whether the operation was successful or not.
-    pub fn update(&mut self, key: Key, value: Value) -> Result<(), SMTError> {
-        let key = key.to_string().parse::<Node>().unwrap();
-        let value = value.to_string().parse::<Node>().unwrap();
-
+    pub fn update(&mut self, key: H::Node, value: H::Node) -> Result<(), SMTError> {
         let EntryResponse {
             entry, siblings, ..
-        } = self.retrieve_entry(key.clone());
+        } = self.retrieve_entry(key.clone())?;
 
         if entry.get(1).is_none() {
-            return Err(SMTError::KeyDoesNotExist(key.to_string()));
+            return Err(SMTError::KeyDoesNotExist(key.to_node_string()));
         }
 
-        let path = key_to_path(&key.to_string());
+        let path = key.to_path(self.depth)?;
 
         // Deletes the old nodes and re-creates them with the new hashes.
-        let old_node = (self.hash)(entry.clone());
+        let old_node = self.hash.hash(entry.clone());
         self.nodes.remove(&old_node);
         self.delete_old_nodes(old_node.clone(), &path, &siblings);
 
-        let new_node = (self.hash)(vec![key.clone(), value.clone(), self.entry_mark.clone()]);
+        let new_node = self
+            .hash
+            .hash(vec![key.clone(), value.clone(), self.entry_mark.clone()]);
         self.nodes
             .insert(new_node.clone(), vec![key, value, self.entry_mark.clone()]);
         self.root = self
@@ -270,22 +589,20 @@ impl SMT {
     /// # Returns
     ///
     /// An `Result` indicating whether the operation was successful or not.
-    pub fn delete(&mut self, key: Key) -> Result<(), SMTError> {
-        let key = key.to_string().parse::<Node>().unwrap();
-
+    pub fn delete(&mut self, key: H::Node) -> Result<(), SMTError> {
         let EntryResponse {
             entry,
             mut siblings,
             ..
-        } = self.retrieve_entry(key.clone());
+        } = self.retrieve_entry(key.clone())?;
 
         if entry.get(1).is_none() {
-            return Err(SMTError::KeyDoesNotExist(key.to_string()));
+            return Err(SMTError::KeyDoesNotExist(key.to_node_string()));
         }
 
-        let path = key_to_path(&key.to_string());
+        let path = key.to_path(self.depth)?;
 
-        let node = (self.hash)(entry.clone());
+        let node = self.hash.hash(entry.clone());
         self.nodes.remove(&node);
 
         self.root = self.zero_node.clone();
@@ -303,15 +620,7 @@ impl SMT {
                     .unwrap();
             } else {
                 let first_sibling = siblings.pop().unwrap();
-                let i = get_index_of_last_non_zero_element(
-                    siblings
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<String>>()
-                        .iter()
-                        .map(|s| s.as_str())
-                        .collect::<Vec<&str>>(),
-                );
+                let i = get_index_of_last_non_zero_element(&siblings, &self.zero_node);
 
                 self.root = self.add_new_nodes(first_sibling, &path, &siblings, Some(i))?;
             }
@@ -328,19 +637,18 @@ impl SMT {
     ///
     /// # Returns
     ///
-    /// A `MerkleProof` containing the proof information.
-    pub fn create_proof(&self, key: Key) -> MerkleProof {
-        let key = key.to_string().parse::<Node>().unwrap();
-
+    /// A `MerkleProof` containing the proof information, or an error if the key does not fit in
+    /// the depth of the tree.
+    pub fn create_proof(&self, key: H::Node) -> Result<MerkleProof<H::Node>, SMTError> {
         let EntryResponse {
             entry,
             matching_entry,
             siblings,
-        } = self.retrieve_entry(key);
+        } = self.retrieve_entry(key)?;
 
         // If the key exists, the function returns a proof with the entry itself, otherwise it returns
         // a non-membership proof with the matching entry.
-        MerkleProof {
+        Ok(MerkleProof {
             entry_response: EntryResponse {
                 entry: entry.clone(),
                 matching_entry,
@@ -348,7 +656,7 @@ impl SMT {
             },
             root: self.root.clone(),
             membership: entry.get(1).is_some(),
-        }
+        })
     }
 
     /// Verifies a membership or a non-membership proof for a given key in the SMT.
@@ -360,20 +668,55 @@ impl SMT {
     /// # Returns
     ///
     /// A boolean indicating whether the proof is valid or not.
-    pub fn verify_proof(&self, merkle_proof: MerkleProof) -> bool {
+    pub fn verify_proof(&self, merkle_proof: &MerkleProof<H::Node>) -> bool {
+        SMT::verify(&self.hash, &self.zero_node, &self.entry_mark, merkle_proof)
+    }
+
+    /// Verifies a membership or a non-membership proof against its root, without a tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hasher of the tree.
+    /// * `zero_node` - The zero node of the tree.
+    /// * `entry_mark` - The entry mark of the tree, i.e. the third element of the leaves.
+    /// * `merkle_proof` - The Merkle proof to verify.
+    ///
+    /// # Returns
+    ///
+    /// A boolean indicating whether the proof is valid or not. Malformed proofs are not valid.
+    pub fn verify(
+        hash: &H,
+        zero_node: &H::Node,
+        entry_mark: &H::Node,
+        merkle_proof: &MerkleProof<H::Node>,
+    ) -> bool {
+        if merkle_proof.validate(entry_mark).is_err() {
+            return false;
+        }
+
+        // The proof does not tell the depth of the tree, but the siblings only use the first bits
+        // of the paths, which are the same for any depth the keys fit in.
+        let entry_response = &merkle_proof.entry_response;
+        let Ok(path) = entry_response.entry[0].to_path(MAX_DEPTH) else {
+            return false;
+        };
+
+        if entry_response.siblings.len() > path.len() {
+            return false;
+        }
+
         // If there is no matching entry, it simply obtains the root hash by using the siblings and the
         // path of the key.
-        if merkle_proof.entry_response.matching_entry.is_none() {
-            let path = key_to_path(&merkle_proof.entry_response.entry[0].to_string());
+        if entry_response.matching_entry.is_none() {
             // If there is not an entry value, the proof is a non-membership proof. In this case, since there
             // is not a matching entry, the node is set to a zero node. If there is an entry value, the proof
             // is a membership proof and the node is set to the hash of the entry.
-            let node = if merkle_proof.entry_response.entry.get(1).is_some() {
-                (self.hash)(merkle_proof.entry_response.entry)
+            let node = if entry_response.entry.get(1).is_some() {
+                hash.hash(entry_response.entry.clone())
             } else {
-                self.zero_node.clone()
+                zero_node.clone()
             };
-            let root = self.calculate_root(node, &path, &merkle_proof.entry_response.siblings);
+            let root = calculate_root(hash, node, &path, &entry_response.siblings);
 
             // If the obtained root is equal to the proof root, then the proof is valid.
             return root == merkle_proof.root;
@@ -382,21 +725,22 @@ impl SMT {
         // If there is a matching entry, the proof is definitely a non-membership proof. In this case, it checks
         // if the matching node belongs to the tree, and then it checks if the number of the first matching bits
         // of the keys is greater than or equal to the number of the siblings.
-        if let Some(matching_entry) = &merkle_proof.entry_response.matching_entry {
-            let matching_path = key_to_path(&matching_entry[0].to_string());
-            let node = (self.hash)(matching_entry.to_vec());
-            let root =
-                self.calculate_root(node, &matching_path, &merkle_proof.entry_response.siblings);
+        if let Some(matching_entry) = &entry_response.matching_entry {
+            let Ok(matching_path) = matching_entry[0].to_path(MAX_DEPTH) else {
+                return false;
+            };
+
+            let node = hash.hash(matching_entry.to_vec());
+            let root = calculate_root(hash, node, &matching_path, &entry_response.siblings);
 
             if root == merkle_proof.root {
-                let path = key_to_path(&merkle_proof.entry_response.entry[0].to_string());
                 // Returns the first common bits of the two keys: the non-member key and the matching key.
                 let first_matching_bits = get_first_common_elements(&path, &matching_path);
 
                 // If the non-member key was a key of a tree entry, the depth of the matching node should be
                 // greater than the number of the fisrt matching bits. Otherwise, the depth of the node can be
                 // defined by the number of its siblings.
-                return merkle_proof.entry_response.siblings.len() <= first_matching_bits.len();
+                return entry_response.siblings.len() <= first_matching_bits.len();
             }
         }
 
@@ -417,10 +761,11 @@ impl SMT {
     ///
     /// # Returns
     ///
-    /// An `EntryResponse` struct containing the entry, the matching entry (if any), and the siblings of the leaf node.
-    fn retrieve_entry(&self, key: Key) -> EntryResponse {
-        let path = key_to_path(&key.to_string());
-        let mut siblings: Siblings = Vec::new();
+    /// An `EntryResponse` struct containing the entry, the matching entry (if any), and the siblings of the leaf node,
+    /// or an error if the key does not fit in the depth of the tree.
+    pub(crate) fn retrieve_entry(&self, key: H::Node) -> Result<EntryResponse<H::Node>, SMTError> {
+        let path = key.to_path(self.depth)?;
+        let mut siblings = Vec::new();
         let mut node = self.root.clone();
 
         let mut i = 0;
@@ -428,31 +773,33 @@ impl SMT {
         // Starting from the root, it traverses the tree until it reaches a leaf node, a zero node,
         // or a matching entry.
         while node != self.zero_node {
-            let child_nodes = self.nodes.get(&node).unwrap_or(&Vec::new()).clone();
-            let direction = path[i];
+            let child_nodes = self.nodes.get(&node).cloned().unwrap_or_default();
 
             // If the third element of the child nodes is not None, it means that the node is an entry of the tree.
             if child_nodes.get(2).is_some() {
                 if child_nodes[0] == key {
                     // An entry is found with the same key, and it returns it with the siblings.
-                    return EntryResponse {
+                    return Ok(EntryResponse {
                         entry: child_nodes,
                         matching_entry: None,
                         siblings,
-                    };
+                    });
                 }
 
                 // An entry was found with a different key, but the key of this particular entry matches the first 'i'
                 // bits of the key passed as parameter. It can be useful in several functions.
-                return EntryResponse {
+                return Ok(EntryResponse {
                     entry: vec![key.clone()],
                     matching_entry: Some(child_nodes),
                     siblings,
-                };
+                });
             }
 
             // When it goes down into the tree and follows the path, in every step a node is chosen between left
-            // and right child nodes, and the opposite node is saved in the `siblings` vector.
+            // and right child nodes, and the opposite node is saved in the `siblings` vector. Only leaves can be
+            // at the last level.
+            let direction = *path.get(i).ok_or(SMTError::DepthExhausted(self.depth))?;
+
             node = child_nodes[direction].clone();
             siblings.push(child_nodes[1 - direction].clone());
 
@@ -460,38 +807,11 @@ impl SMT {
         }
 
         // The path led to a zero node.
-        EntryResponse {
+        Ok(EntryResponse {
             entry: vec![key],
             matching_entry: None,
             siblings,
-        }
-    }
-
-    /// Calculates the root of the tree by using the given node, the path, and the siblings.
-    ///
-    /// It calculates with a bottom up approach by starting from the node and going up to the root.
-    ///
-    /// # Arguments
-    ///
-    /// * `node` - The node to start the calculation from.
-    /// * `path` - The path of the key.
-    /// * `siblings` - The siblings of the path.
-    ///
-    /// # Returns
-    ///
-    /// The root of the tree.
-    fn calculate_root(&self, mut node: Node, path: &[usize], siblings: &Siblings) -> Node {
-        for i in (0..siblings.len()).rev() {
-            let child_nodes: ChildNodes = if path[i] != 0 {
-                vec![siblings[i].clone(), node.clone()]
-            } else {
-                vec![node.clone(), siblings[i].clone()]
-            };
-
-            node = (self.hash)(child_nodes);
-        }
-
-        node
+        })
     }
 
     /// Adds new nodes to the tree with the new hashes.
@@ -510,29 +830,31 @@ impl SMT {
     /// The new root of the tree.
     fn add_new_nodes(
         &mut self,
-        mut node: Node,
+        mut node: H::Node,
         path: &[usize],
-        siblings: &Siblings,
+        siblings: &[H::Node],
         i: Option<isize>,
-    ) -> Result<Node, SMTError> {
+    ) -> Result<H::Node, SMTError> {
         let mut starting_index = if let Some(i) = i {
             i
         } else {
             siblings.len() as isize - 1
         };
 
-        while starting_index > 0 {
-            if siblings.get(starting_index as usize).is_none() {
+        while starting_index >= 0 {
+            let i = starting_index as usize;
+
+            if siblings.get(i).is_none() {
                 return Err(SMTError::InvalidSiblingIndex);
             }
 
-            let child_nodes: ChildNodes = if path.get(starting_index as usize).is_some() {
-                vec![siblings[starting_index as usize].clone(), node.clone()]
+            let child_nodes = if path[i] != 0 {
+                vec![siblings[i].clone(), node.clone()]
             } else {
-                vec![node.clone(), siblings[starting_index as usize].clone()]
+                vec![node.clone(), siblings[i].clone()]
             };
 
-            node = (self.hash)(child_nodes.clone());
+            node = self.hash.hash(child_nodes.clone());
 
             self.nodes.insert(node.clone(), child_nodes);
 
@@ -551,15 +873,15 @@ impl SMT {
     /// * `node` - The node to start the calculation from.
     /// * `path` - The path of the key.
     /// * `siblings` - The siblings of the path.
-    fn delete_old_nodes(&mut self, mut node: Node, path: &[usize], siblings: &Siblings) {
+    fn delete_old_nodes(&mut self, mut node: H::Node, path: &[usize], siblings: &[H::Node]) {
         for i in (0..siblings.len()).rev() {
-            let child_nodes: ChildNodes = if path.get(i).is_some() {
+            let child_nodes = if path[i] != 0 {
                 vec![siblings[i].clone(), node.clone()]
             } else {
                 vec![node.clone(), siblings[i].clone()]
             };
 
-            node = (self.hash)(child_nodes);
+            node = self.hash.hash(child_nodes);
 
             self.nodes.remove(&node);
         }
@@ -574,7 +896,7 @@ impl SMT {
     /// # Returns
     ///
     /// A boolean indicating whether the node is a leaf node or not.
-    fn is_leaf(&self, node: &Node) -> bool {
+    pub(crate) fn is_leaf(&self, node: &H::Node) -> bool {
         if let Some(child_nodes) = self.nodes.get(node) {
             child_nodes.get(2).is_some()
         } else {
@@ -583,6 +905,38 @@ impl SMT {
     }
 }
 
+/// Calculates the root of a tree from a node, the path of its key and its siblings, with the
+/// given hasher.
+///
+/// # Arguments
+///
+/// * `hash` - The hasher used to hash the child nodes.
+/// * `node` - The node to start the calculation from.
+/// * `path` - The path of the key.
+/// * `siblings` - The siblings of the path, which must not be longer than the path.
+///
+/// # Returns
+///
+/// The root of the tree.
+pub(crate) fn calculate_root<H: SmtHasher>(
+    hash: &H,
+    mut node: H::Node,
+    path: &[usize],
+    siblings: &[H::Node],
+) -> H::Node {
+    for i in (0..siblings.len()).rev() {
+        let child_nodes = if path[i] != 0 {
+            vec![siblings[i].clone(), node.clone()]
+        } else {
+            vec![node.clone(), siblings[i].clone()]
+        };
+
+        node = hash.hash(child_nodes);
+    }
+
+    node
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -595,14 +949,12 @@ mod tests {
     #[test]
     fn test_new() {
         let smt = SMT::new(hash_function, false);
-        assert!(!smt.big_numbers);
         assert_eq!(smt.zero_node, Node::Str("0".to_string()));
         assert_eq!(smt.entry_mark, Node::Str("1".to_string()));
         assert_eq!(smt.nodes, HashMap::new());
         assert_eq!(smt.root, Node::Str("0".to_string()));
 
         let smt = SMT::new(hash_function, true);
-        assert!(smt.big_numbers);
         assert_eq!(smt.zero_node, Node::BigInt(BigInt::from(0)));
         assert_eq!(smt.entry_mark, Node::BigInt(BigInt::from(1)));
         assert_eq!(smt.nodes, HashMap::new());
@@ -729,14 +1081,14 @@ mod tests {
         let key = Key::Str("abc".to_string());
         let value = Value::Str("123".to_string());
         let _ = smt.add(key.clone(), value.clone());
-        let proof = smt.create_proof(key.clone());
+        let proof = smt.create_proof(key.clone()).unwrap();
         assert_eq!(proof.root, smt.root);
 
         let mut smt = SMT::new(hash_function, true);
         let key = Key::BigInt(BigInt::from(123));
         let value = Value::BigInt(BigInt::from(456));
         let _ = smt.add(key.clone(), value.clone());
-        let proof = smt.create_proof(key.clone());
+        let proof = smt.create_proof(key.clone()).unwrap();
         assert_eq!(proof.root, smt.root);
     }
 
@@ -746,8 +1098,8 @@ mod tests {
         let key = Key::Str("abc".to_string());
         let value = Value::Str("123".to_string());
         let _ = smt.add(key.clone(), value.clone());
-        let proof = smt.create_proof(key.clone());
-        let result = smt.verify_proof(proof);
+        let proof = smt.create_proof(key.clone()).unwrap();
+        let result = smt.verify_proof(&proof);
         assert!(result);
 
         let key2 = Key::Str("def".to_string());
@@ -760,15 +1112,15 @@ mod tests {
             root: smt.root.clone(),
             membership: false,
         };
-        let fun = smt.verify_proof(false_proof);
+        let fun = smt.verify_proof(&false_proof);
         assert!(!fun);
 
         let mut smt = SMT::new(hash_function, true);
         let key = Key::BigInt(BigInt::from(123));
         let value = Value::BigInt(BigInt::from(456));
         let _ = smt.add(key.clone(), value.clone());
-        let proof = smt.create_proof(key.clone());
-        let result = smt.verify_proof(proof);
+        let proof = smt.create_proof(key.clone()).unwrap();
+        let result = smt.verify_proof(&proof);
         assert!(result);
 
         let key2 = Key::BigInt(BigInt::from(789));
@@ -781,7 +1133,7 @@ mod tests {
             root: smt.root.clone(),
             membership: true,
         };
-        let fun = smt.verify_proof(false_proof);
+        let fun = smt.verify_proof(&false_proof);
         assert!(!fun);
     }
 
@@ -789,14 +1141,14 @@ mod tests {
     fn test_retrieve_entry() {
         let smt = SMT::new(hash_function, false);
         let key = Key::Str("be12".to_string());
-        let entry_response = smt.retrieve_entry(key.clone());
+        let entry_response = smt.retrieve_entry(key.clone()).unwrap();
         assert_eq!(entry_response.entry, vec![key]);
         assert_eq!(entry_response.matching_entry, None);
         assert_eq!(entry_response.siblings, Vec::new());
 
         let smt = SMT::new(hash_function, true);
         let key = Key::BigInt(BigInt::from(123));
-        let entry_response = smt.retrieve_entry(key.clone());
+        let entry_response = smt.retrieve_entry(key.clone()).unwrap();
         assert_eq!(entry_response.entry, vec![key]);
         assert_eq!(entry_response.matching_entry, None);
         assert_eq!(entry_response.siblings, Vec::new());
@@ -812,7 +1164,7 @@ mod tests {
             Node::Str("sibling2".to_string()),
             Node::Str("sibling3".to_string()),
         ];
-        let root = smt.calculate_root(node.clone(), path, &siblings);
+        let root = calculate_root(&smt.hash, node.clone(), path, &siblings);
         assert_eq!(
             root,
             Node::Str("sibling2,node,sibling3,sibling1".to_string())
@@ -825,7 +1177,7 @@ mod tests {
             Node::BigInt(BigInt::from(456)),
             Node::BigInt(BigInt::from(789)),
         ];
-        let root = smt.calculate_root(node.clone(), path, &siblings);
+        let root = calculate_root(&smt.hash, node.clone(), path, &siblings);
         assert_eq!(root, Node::Str("456,123,789".to_string()));
     }
 
@@ -842,12 +1194,18 @@ mod tests {
         let new_node = smt
             .add_new_nodes(node.clone(), path, &siblings, None)
             .unwrap();
-        assert_eq!(new_node, Node::Str("sibling2,sibling3,node".to_string()));
+        assert_eq!(
+            new_node,
+            Node::Str("sibling2,node,sibling3,sibling1".to_string())
+        );
 
         let starting_index = smt
             .add_new_nodes(node.clone(), path, &siblings, Some(1))
             .unwrap();
-        assert_eq!(starting_index, Node::Str("sibling2,node".to_string()));
+        assert_eq!(
+            starting_index,
+            Node::Str("sibling2,node,sibling1".to_string())
+        );
 
         let mut smt = SMT::new(hash_function, true);
         let node = Node::BigInt(BigInt::from(111));
@@ -860,12 +1218,12 @@ mod tests {
         let new_node = smt
             .add_new_nodes(node.clone(), path, &siblings, None)
             .unwrap();
-        assert_eq!(new_node, Node::Str("333,444,111".to_string()));
+        assert_eq!(new_node, Node::Str("222,111,444,333".to_string()));
 
         let starting_index = smt
             .add_new_nodes(node.clone(), path, &siblings, Some(1))
             .unwrap();
-        assert_eq!(starting_index, Node::Str("333,111".to_string()));
+        assert_eq!(starting_index, Node::Str("222,111,333".to_string()));
     }
 
     #[test]
@@ -881,7 +1239,10 @@ mod tests {
         let new_node = smt
             .add_new_nodes(node.clone(), path, &siblings, None)
             .unwrap();
-        assert_eq!(new_node, Node::Str("sibling2,sibling3,abc".to_string()));
+        assert_eq!(
+            new_node,
+            Node::Str("sibling2,abc,sibling3,sibling1".to_string())
+        );
         smt.delete_old_nodes(node.clone(), path, &siblings);
         assert_eq!(smt.nodes.len(), 0);
 
@@ -895,7 +1256,7 @@ mod tests {
         let new_node = smt
             .add_new_nodes(node.clone(), path, &siblings, None)
             .unwrap();
-        assert_eq!(new_node, Node::Str("789,123".to_string()));
+        assert_eq!(new_node, Node::Str("456,123,789".to_string()));
         smt.delete_old_nodes(node.clone(), path, &siblings);
         assert_eq!(smt.nodes.len(), 0);
     }
@@ -930,4 +1291,408 @@ mod tests {
         );
         assert!(smt.is_leaf(&node));
     }
+
+    struct BytesHasher;
+
+    impl SmtHasher for BytesHasher {
+        type Node = [u8; 32];
+
+        fn hash(&self, nodes: Vec<[u8; 32]>) -> [u8; 32] {
+            use std::hash::{DefaultHasher, Hasher};
+
+            let mut result = [0u8; 32];
+
+            for (i, chunk) in result.chunks_mut(8).enumerate() {
+                let mut hasher = DefaultHasher::new();
+                i.hash(&mut hasher);
+                nodes.hash(&mut hasher);
+                chunk.copy_from_slice(&hasher.finish().to_be_bytes());
+            }
+
+            result
+        }
+    }
+
+    struct FieldHasher;
+
+    impl SmtHasher for FieldHasher {
+        type Node = Fr;
+
+        fn hash(&self, nodes: Vec<Fr>) -> Fr {
+            nodes.into_iter().fold(Fr::from(7u64), |acc, node| {
+                acc * acc * Fr::from(31u64) + node
+            })
+        }
+    }
+
+    /// Adds the keys in order, checks every proof and deletes the keys in reverse order.
+    fn check_tree<H: SmtHasher>(hash: H, keys: Vec<H::Node>, value: H::Node) {
+        let mut smt = SMT::with_hasher(hash);
+        let mut roots = vec![smt.root()];
+
+        for key in &keys {
+            smt.add(key.clone(), value.clone()).unwrap();
+            roots.push(smt.root());
+        }
+
+        for key in &keys {
+            assert_eq!(smt.get(key.clone()), Some(value.clone()));
+            assert!(smt.verify_proof(&smt.create_proof(key.clone()).unwrap()));
+        }
+
+        for key in keys.iter().rev() {
+            assert_eq!(roots.pop(), Some(smt.root()));
+            smt.delete(key.clone()).unwrap();
+            assert_eq!(smt.get(key.clone()), None);
+            assert!(smt.verify_proof(&smt.create_proof(key.clone()).unwrap()));
+        }
+
+        assert_eq!(smt.root(), H::Node::zero());
+        assert!(smt.nodes.is_empty());
+    }
+
+    #[test]
+    fn test_multiple_entries() {
+        let hash: HashFunction = hash_function;
+        let keys = [2, 6, 7, 18, 1, 1024, 255]
+            .into_iter()
+            .map(|key| Node::BigInt(BigInt::from(key)))
+            .collect();
+
+        check_tree(hash, keys, Node::BigInt(BigInt::from(10)));
+    }
+
+    #[test]
+    fn test_root_does_not_depend_on_order() {
+        let keys = [3, 11, 19, 4, 100];
+        let mut smt = SMT::new(hash_function, true);
+        let mut reversed = SMT::new(hash_function, true);
+
+        for key in keys {
+            smt.add(
+                Key::BigInt(BigInt::from(key)),
+                Value::BigInt(BigInt::from(1)),
+            )
+            .unwrap();
+        }
+
+        for key in keys.into_iter().rev() {
+            reversed
+                .add(
+                    Key::BigInt(BigInt::from(key)),
+                    Value::BigInt(BigInt::from(1)),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(smt.root(), reversed.root());
+
+        let proof = smt.create_proof(Key::BigInt(BigInt::from(27))).unwrap();
+        assert!(!proof.membership);
+        assert!(smt.verify_proof(&proof));
+    }
+
+    #[test]
+    fn test_string_and_bigint_keys() {
+        let mut smt = SMT::new(hash_function, true);
+        smt.add(Key::Str("12".to_string()), Value::Str("34".to_string()))
+            .unwrap();
+        smt.add(
+            Key::BigInt(BigInt::from(12)),
+            Value::BigInt(BigInt::from(56)),
+        )
+        .unwrap();
+
+        assert_eq!(
+            smt.get(Key::Str("12".to_string())),
+            Some(Value::Str("34".to_string()))
+        );
+        assert_eq!(
+            smt.get(Key::BigInt(BigInt::from(12))),
+            Some(Value::BigInt(BigInt::from(56)))
+        );
+    }
+
+    #[test]
+    fn test_bytes_nodes() {
+        let keys = (0..20u8)
+            .map(|i| {
+                let mut key = [0u8; 32];
+                key[31] = i.wrapping_mul(37);
+                key[0] = i;
+                key
+            })
+            .collect();
+
+        check_tree(BytesHasher, keys, <[u8; 32]>::one());
+        assert_eq!(<[u8; 32]>::one().to_path(MAX_DEPTH).unwrap()[..2], [1, 0]);
+
+        let mut smt = SMT::with_hasher(BytesHasher);
+        smt.add([1; 32], [2; 32]).unwrap();
+        assert_eq!(
+            smt.add([1; 32], [3; 32]),
+            Err(SMTError::KeyAlreadyExist("01".repeat(32)))
+        );
+    }
+
+    #[test]
+    fn test_field_nodes() {
+        let keys = (1..30u64).map(|i| Fr::from(i * i * 7919)).collect();
+
+        check_tree(FieldHasher, keys, Fr::from(42u64));
+        assert_eq!(
+            Fr::from(6u64).to_path(MAX_DEPTH).unwrap()[..4],
+            [0, 1, 1, 0]
+        );
+        assert_eq!(Fr::from(6u64).to_path(MAX_DEPTH).unwrap().len(), 256);
+    }
+
+    #[test]
+    fn test_proof_accessors() {
+        let mut smt = SMT::new(hash_function, true);
+        let key = Key::BigInt(BigInt::from(123));
+        let value = Value::BigInt(BigInt::from(456));
+        smt.add(key.clone(), value.clone()).unwrap();
+        smt.add(Key::BigInt(BigInt::from(124)), value.clone())
+            .unwrap();
+
+        let proof = smt.create_proof(key.clone()).unwrap();
+        assert!(proof.membership());
+        assert_eq!(proof.root(), &smt.root());
+        assert_eq!(proof.key(), &key);
+        assert_eq!(proof.value(), Some(&value));
+        assert_eq!(proof.entry().len(), 3);
+        assert_eq!(proof.matching_entry(), None);
+        assert_eq!(proof.siblings().len(), 1);
+
+        let proof = smt.create_proof(Key::BigInt(BigInt::from(789))).unwrap();
+        assert!(!proof.membership());
+        assert_eq!(proof.value(), None);
+    }
+
+    #[test]
+    fn test_new_proof() {
+        let key = Node::BigInt(BigInt::from(1));
+        let root = Node::BigInt(BigInt::from(0));
+        let entry = vec![key.clone(), key.clone(), key.clone()];
+
+        assert!(MerkleProof::new(root.clone(), entry.clone(), None, vec![], &key).is_ok());
+        assert!(MerkleProof::new(root.clone(), vec![key.clone()], None, vec![], &key).is_ok());
+        assert_eq!(
+            MerkleProof::new(root.clone(), vec![key.clone(); 2], None, vec![], &key).err(),
+            Some(SMTError::MalformedProof(
+                "The entry must be a key or a key, a value and an entry mark"
+            ))
+        );
+        assert_eq!(
+            MerkleProof::new(root.clone(), entry.clone(), None, vec![], &root).err(),
+            Some(SMTError::MalformedProof(
+                "The entry must end with the entry mark"
+            ))
+        );
+        assert_eq!(
+            MerkleProof::new(
+                root.clone(),
+                entry.clone(),
+                Some(entry.clone()),
+                vec![],
+                &key
+            )
+            .err(),
+            Some(SMTError::MalformedProof(
+                "A membership proof cannot have a matching entry"
+            ))
+        );
+        assert_eq!(
+            MerkleProof::new(root.clone(), vec![key.clone()], Some(entry), vec![], &key).err(),
+            Some(SMTError::MalformedProof(
+                "The matching entry must be the entry of another key"
+            ))
+        );
+        assert_eq!(
+            MerkleProof::new(
+                root.clone(),
+                vec![root.clone()],
+                Some(vec![key.clone(), key.clone(), root.clone()]),
+                vec![],
+                &key
+            )
+            .err(),
+            Some(SMTError::MalformedProof(
+                "The matching entry must be the entry of another key"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_verify_without_tree() {
+        let mut smt = SMT::new(hash_function, true);
+
+        for key in [3, 11, 19, 4] {
+            smt.add(
+                Key::BigInt(BigInt::from(key)),
+                Value::BigInt(BigInt::from(1)),
+            )
+            .unwrap();
+        }
+
+        let zero_node = Node::BigInt(BigInt::from(0));
+        let entry_mark = Node::BigInt(BigInt::from(1));
+
+        // A light client rebuilds the proofs from their parts and only knows the root.
+        for key in [3, 19, 27, 8] {
+            let proof = smt.create_proof(Key::BigInt(BigInt::from(key))).unwrap();
+            let rebuilt = MerkleProof::new(
+                smt.root(),
+                proof.entry().to_vec(),
+                proof.matching_entry().map(|entry| entry.to_vec()),
+                proof.siblings().to_vec(),
+                &entry_mark,
+            )
+            .unwrap();
+
+            assert!(SMT::verify(
+                &(hash_function as HashFunction),
+                &zero_node,
+                &entry_mark,
+                &rebuilt
+            ));
+        }
+
+        let proof = smt.create_proof(Key::BigInt(BigInt::from(3))).unwrap();
+        let forged = MerkleProof::new(
+            smt.root(),
+            vec![
+                Key::BigInt(BigInt::from(3)),
+                Value::BigInt(BigInt::from(2)),
+                Node::BigInt(BigInt::from(1)),
+            ],
+            None,
+            proof.siblings().to_vec(),
+            &entry_mark,
+        )
+        .unwrap();
+
+        assert!(!SMT::verify(
+            &(hash_function as HashFunction),
+            &zero_node,
+            &entry_mark,
+            &forged
+        ));
+    }
+
+    #[test]
+    fn test_key_paths() {
+        let mut bytes = [0u8; 32];
+        bytes[30] = 0x01;
+        bytes[31] = 0x2c;
+        let path = Node::BigInt(BigInt::from(300)).to_path(MAX_DEPTH).unwrap();
+
+        // BigInt keys are read by value and string keys as hexadecimal numbers.
+        assert_eq!(&path[..9], [0, 0, 1, 1, 0, 1, 0, 0, 1]);
+        assert_ne!(
+            Node::Str("300".to_string()).to_path(MAX_DEPTH),
+            Node::Str("12c".to_string()).to_path(MAX_DEPTH)
+        );
+        assert_eq!(
+            Node::Str("300".to_string()).to_path(MAX_DEPTH),
+            Node::BigInt(BigInt::from(0x300)).to_path(MAX_DEPTH)
+        );
+        assert_eq!(
+            Node::Str("12c".to_string()).to_path(MAX_DEPTH),
+            Ok(path.clone())
+        );
+        assert_eq!(
+            Node::Str("012C".to_string()).to_path(MAX_DEPTH),
+            Ok(path.clone())
+        );
+        assert_eq!(bytes.to_path(MAX_DEPTH), Ok(path.clone()));
+        assert_eq!(Fr::from(300u64).to_path(MAX_DEPTH), Ok(path.clone()));
+
+        assert_eq!(
+            Node::BigInt(BigInt::from(300)).to_path(64),
+            Ok(path[..64].to_vec())
+        );
+        assert_eq!(Node::Str("ff".to_string()).to_path(8), Ok(vec![1; 8]));
+        assert_eq!(
+            Node::Str("100".to_string()).to_path(6),
+            Err(SMTError::KeyOutOfRange("100".to_string(), 6))
+        );
+        assert_eq!(
+            [0xff; 32].to_path(254),
+            Err(SMTError::KeyOutOfRange("ff".repeat(32), 254))
+        );
+        assert!(Node::BigInt(BigInt::from(-1)).to_path(MAX_DEPTH).is_err());
+        assert!(Node::Str("xyz".to_string()).to_path(MAX_DEPTH).is_err());
+        assert!(Node::Str(String::new()).to_path(MAX_DEPTH).is_err());
+    }
+
+    #[test]
+    fn test_with_depth() {
+        let hash: HashFunction = hash_function;
+
+        assert_eq!(
+            SMT::with_depth(hash, 0).err(),
+            Some(SMTError::InvalidDepth(0))
+        );
+        assert_eq!(
+            SMT::with_depth(hash, MAX_DEPTH + 1).err(),
+            Some(SMTError::InvalidDepth(MAX_DEPTH + 1))
+        );
+
+        for depth in [64, 160, 254, 256] {
+            let smt = SMT::with_depth(hash, depth).unwrap();
+            assert_eq!(smt.depth(), depth);
+        }
+
+        let mut smt = SMT::with_depth(hash, 8).unwrap();
+        let value = Value::BigInt(BigInt::from(1));
+        let keys = (0..=255).map(|key| Key::BigInt(BigInt::from(key)));
+
+        // Every key of 8 bits fits in the tree, and the leaves can be at the last level.
+        for key in keys.clone() {
+            smt.add(key, value.clone()).unwrap();
+        }
+
+        for key in keys {
+            assert!(smt.verify_proof(&smt.create_proof(key).unwrap()));
+        }
+
+        let key = Key::BigInt(BigInt::from(256));
+        let error = || SMTError::KeyOutOfRange("256".to_string(), 8);
+
+        assert_eq!(smt.add(key.clone(), value.clone()), Err(error()));
+        assert_eq!(smt.update(key.clone(), value), Err(error()));
+        assert_eq!(smt.delete(key.clone()), Err(error()));
+        assert_eq!(smt.create_proof(key.clone()).err(), Some(error()));
+        assert_eq!(smt.get(key), None);
+
+        for key in 0..=255 {
+            smt.delete(Key::BigInt(BigInt::from(key))).unwrap();
+        }
+
+        assert_eq!(smt.root(), Node::zero());
+    }
+
+    #[test]
+    fn test_keys_with_the_same_path() {
+        let mut smt = SMT::new(hash_function, true);
+        smt.add(Key::Str("a".to_string()), Value::BigInt(BigInt::from(1)))
+            .unwrap();
+        let root = smt.root();
+
+        // `Str("a")` and `BigInt(10)` are different keys with the same path.
+        assert_eq!(
+            smt.add(
+                Key::BigInt(BigInt::from(10)),
+                Value::BigInt(BigInt::from(2))
+            ),
+            Err(SMTError::DepthExhausted(MAX_DEPTH))
+        );
+        assert_eq!(smt.root(), root);
+        assert_eq!(
+            smt.get(Key::Str("a".to_string())),
+            Some(Value::BigInt(BigInt::from(1)))
+        );
+    }
 }
@@ -0,0 +1,545 @@
+use crate::smt::{
+    calculate_root, EntryResponse, Node, SMTError, SmtHasher, SmtNode, MAX_DEPTH, SMT,
+};
+
+/// A mutation of the tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SMTOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl SMTOperation {
+    /// Returns the `fnc` input of circomlib's `SMTProcessor`: `[1, 0]` to insert, `[0, 1]` to
+    /// update and `[1, 1]` to delete.
+    pub fn fnc(&self) -> [u8; 2] {
+        match self {
+            SMTOperation::Insert => [1, 0],
+            SMTOperation::Update => [0, 1],
+            SMTOperation::Delete => [1, 1],
+        }
+    }
+}
+
+/// Proof that an operation moved the root of a tree from `old_root` to `new_root`, with the
+/// fields of circomlib's `SMTProcessor` and circomlibjs' `insert`, `update` and `delete`.
+///
+/// The siblings are the path to the position where the keys of an insertion or a deletion
+/// diverge, without trailing zeros. `old_key` and `old_value` are the entry found at that
+/// position before an insertion or left there after a deletion, and are zero when
+/// `is_old0` is true. A deletion is proved as the insertion of the deleted entry from the new
+/// root to the old root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransitionProof<N = Node> {
+    pub operation: SMTOperation,
+    pub old_root: N,
+    pub new_root: N,
+    pub siblings: Vec<N>,
+    pub old_key: N,
+    pub old_value: N,
+    pub is_old0: bool,
+    /// The inserted, updated or deleted key.
+    pub new_key: N,
+    /// The inserted value, the new value of an update or the deleted value.
+    pub new_value: N,
+}
+
+impl<H: SmtHasher> SMT<H> {
+    /// Adds a new key-value pair to the SMT and proves the transition.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to add.
+    /// * `value` - The value associated with the key.
+    ///
+    /// # Returns
+    ///
+    /// The proof of the insertion, or an error if the key already exists.
+    pub fn add_with_proof(
+        &mut self,
+        key: H::Node,
+        value: H::Node,
+    ) -> Result<TransitionProof<H::Node>, SMTError> {
+        let old_root = self.root();
+
+        let EntryResponse {
+            matching_entry,
+            siblings,
+            ..
+        } = self.retrieve_entry(key.clone())?;
+
+        self.add(key.clone(), value.clone())?;
+
+        let (old_key, old_value, is_old0) = match matching_entry {
+            Some(matching_entry) => (matching_entry[0].clone(), matching_entry[1].clone(), false),
+            None => (self.zero_node.clone(), self.zero_node.clone(), true),
+        };
+
+        Ok(TransitionProof {
+            operation: SMTOperation::Insert,
+            old_root,
+            new_root: self.root(),
+            siblings: self.trim_zeros(siblings),
+            old_key,
+            old_value,
+            is_old0,
+            new_key: key,
+            new_value: value,
+        })
+    }
+
+    /// Updates the value associated with the given key in the SMT and proves the transition.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to update the value for.
+    /// * `value` - The new value associated with the key.
+    ///
+    /// # Returns
+    ///
+    /// The proof of the update, or an error if the key does not exist.
+    pub fn update_with_proof(
+        &mut self,
+        key: H::Node,
+        value: H::Node,
+    ) -> Result<TransitionProof<H::Node>, SMTError> {
+        let old_root = self.root();
+
+        let EntryResponse {
+            entry, siblings, ..
+        } = self.retrieve_entry(key.clone())?;
+
+        self.update(key.clone(), value.clone())?;
+
+        Ok(TransitionProof {
+            operation: SMTOperation::Update,
+            old_root,
+            new_root: self.root(),
+            siblings: self.trim_zeros(siblings),
+            old_key: key.clone(),
+            old_value: entry[1].clone(),
+            is_old0: false,
+            new_key: key,
+            new_value: value,
+        })
+    }
+
+    /// Deletes the key-value pair associated with the given key from the SMT and proves the
+    /// transition.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to delete.
+    ///
+    /// # Returns
+    ///
+    /// The proof of the deletion, or an error if the key does not exist.
+    pub fn delete_with_proof(
+        &mut self,
+        key: H::Node,
+    ) -> Result<TransitionProof<H::Node>, SMTError> {
+        let old_root = self.root();
+
+        let EntryResponse {
+            entry,
+            mut siblings,
+            ..
+        } = self.retrieve_entry(key.clone())?;
+
+        // When the last sibling is a leaf, it moves up to the last non-zero sibling and it is
+        // the entry left at the position of the deleted key.
+        let moved_entry = siblings
+            .last()
+            .filter(|sibling| self.is_leaf(sibling))
+            .map(|sibling| self.nodes[sibling].clone());
+
+        self.delete(key.clone())?;
+
+        let (old_key, old_value, is_old0) = match moved_entry {
+            Some(moved_entry) => {
+                siblings.pop();
+
+                (moved_entry[0].clone(), moved_entry[1].clone(), false)
+            },
+            None => (self.zero_node.clone(), self.zero_node.clone(), true),
+        };
+
+        Ok(TransitionProof {
+            operation: SMTOperation::Delete,
+            old_root,
+            new_root: self.root(),
+            siblings: self.trim_zeros(siblings),
+            old_key,
+            old_value,
+            is_old0,
+            new_key: key,
+            new_value: entry[1].clone(),
+        })
+    }
+
+    /// Verifies a transition proof without a tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hasher of the tree.
+    /// * `zero_node` - The zero node of the tree.
+    /// * `entry_mark` - The entry mark of the tree, i.e. the third element of the leaves.
+    /// * `depth` - The depth of the tree, i.e. the number of bits of its keys.
+    /// * `proof` - The transition proof to verify.
+    ///
+    /// # Returns
+    ///
+    /// A boolean indicating whether the operation moves the old root to the new root. Keys
+    /// that do not fit in the depth are rejected, as well as entries that would diverge below it.
+    pub fn verify_transition(
+        hash: &H,
+        zero_node: &H::Node,
+        entry_mark: &H::Node,
+        depth: usize,
+        proof: &TransitionProof<H::Node>,
+    ) -> bool {
+        if depth > MAX_DEPTH {
+            return false;
+        }
+
+        let Ok(path) = proof.new_key.to_path(depth) else {
+            return false;
+        };
+
+        if proof.siblings.len() > path.len() {
+            return false;
+        }
+
+        match proof.operation {
+            SMTOperation::Insert => verify_insertion(
+                hash,
+                zero_node,
+                entry_mark,
+                &path,
+                proof,
+                &proof.old_root,
+                &proof.new_root,
+            ),
+            SMTOperation::Delete => verify_insertion(
+                hash,
+                zero_node,
+                entry_mark,
+                &path,
+                proof,
+                &proof.new_root,
+                &proof.old_root,
+            ),
+            SMTOperation::Update => {
+                if proof.is_old0 || proof.old_key != proof.new_key {
+                    return false;
+                }
+
+                let old_leaf = hash.hash(vec![
+                    proof.new_key.clone(),
+                    proof.old_value.clone(),
+                    entry_mark.clone(),
+                ]);
+                let new_leaf = hash.hash(vec![
+                    proof.new_key.clone(),
+                    proof.new_value.clone(),
+                    entry_mark.clone(),
+                ]);
+
+                calculate_root(hash, old_leaf, &path, &proof.siblings) == proof.old_root
+                    && calculate_root(hash, new_leaf, &path, &proof.siblings) == proof.new_root
+            },
+        }
+    }
+
+    /// Removes the trailing zero siblings.
+    fn trim_zeros(&self, mut siblings: Vec<H::Node>) -> Vec<H::Node> {
+        while siblings.last() == Some(&self.zero_node) {
+            siblings.pop();
+        }
+
+        siblings
+    }
+}
+
+/// Verifies that inserting the new entry of the proof, whose key has the given path, moves the
+/// tree from `before` to `after`.
+fn verify_insertion<H: SmtHasher>(
+    hash: &H,
+    zero_node: &H::Node,
+    entry_mark: &H::Node,
+    path: &[usize],
+    proof: &TransitionProof<H::Node>,
+    before: &H::Node,
+    after: &H::Node,
+) -> bool {
+    let depth = proof.siblings.len();
+    let new_leaf = hash.hash(vec![
+        proof.new_key.clone(),
+        proof.new_value.clone(),
+        entry_mark.clone(),
+    ]);
+
+    // As in circomlib's `SMTProcessor`, there is no old entry, so its fields must be zero.
+    if proof.is_old0 {
+        return proof.old_key == *zero_node
+            && proof.old_value == *zero_node
+            && calculate_root(hash, zero_node.clone(), path, &proof.siblings) == *before
+            && calculate_root(hash, new_leaf, path, &proof.siblings) == *after;
+    }
+
+    // The old entry must be at the position of the new key, and the keys must diverge below it.
+    let Ok(old_path) = proof.old_key.to_path(path.len()) else {
+        return false;
+    };
+
+    if old_path.get(..depth) != path.get(..depth) {
+        return false;
+    }
+
+    let Some(divergence) =
+        (depth..path.len().min(old_path.len())).find(|&i| path[i] != old_path[i])
+    else {
+        return false;
+    };
+
+    let old_leaf = hash.hash(vec![
+        proof.old_key.clone(),
+        proof.old_value.clone(),
+        entry_mark.clone(),
+    ]);
+
+    if calculate_root(hash, old_leaf.clone(), path, &proof.siblings) != *before {
+        return false;
+    }
+
+    let mut node = if path[divergence] != 0 {
+        hash.hash(vec![old_leaf, new_leaf])
+    } else {
+        hash.hash(vec![new_leaf, old_leaf])
+    };
+
+    for i in (depth..divergence).rev() {
+        node = if path[i] != 0 {
+            hash.hash(vec![zero_node.clone(), node])
+        } else {
+            hash.hash(vec![node, zero_node.clone()])
+        };
+    }
+
+    calculate_root(hash, node, path, &proof.siblings) == *after
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon::{PoseidonSMT, PoseidonSmtHasher};
+    use crate::smt::HashFunction;
+    use ark_bn254::Fr;
+    use num_bigint::BigInt;
+
+    fn hash_function(nodes: Vec<Node>) -> Node {
+        let strings: Vec<String> = nodes.iter().map(|node| node.to_string()).collect();
+        Node::Str(format!("H({})", strings.join(",")))
+    }
+
+    fn node(n: u64) -> Node {
+        Node::BigInt(BigInt::from(n))
+    }
+
+    fn verify(proof: &TransitionProof) -> bool {
+        let hash: HashFunction = hash_function;
+
+        SMT::verify_transition(&hash, &node(0), &node(1), MAX_DEPTH, proof)
+    }
+
+    #[test]
+    fn test_transitions() {
+        let mut smt = SMT::new(hash_function, true);
+        let keys = [5, 1, 13, 8, 24, 3, 2, 16];
+
+        for key in keys {
+            let old_root = smt.root();
+            let proof = smt.add_with_proof(node(key), node(key + 100)).unwrap();
+
+            assert_eq!(proof.old_root, old_root);
+            assert_eq!(proof.new_root, smt.root());
+            assert!(verify(&proof));
+        }
+
+        for key in keys {
+            let proof = smt.update_with_proof(node(key), node(key + 200)).unwrap();
+
+            assert_eq!(proof.old_value, node(key + 100));
+            assert!(verify(&proof));
+        }
+
+        for key in keys {
+            let proof = smt.delete_with_proof(node(key)).unwrap();
+
+            assert_eq!(proof.new_value, node(key + 200));
+            assert_eq!(proof.new_root, smt.root());
+            assert!(verify(&proof));
+        }
+
+        assert_eq!(smt.root(), node(0));
+    }
+
+    #[test]
+    fn test_deletion_reverses_insertion() {
+        let mut smt = SMT::new(hash_function, true);
+
+        for key in [5, 1, 13] {
+            smt.add(node(key), node(key)).unwrap();
+        }
+
+        // 9 diverges from 1, 5 and 13 below the root, next to 1.
+        for key in [9, 3] {
+            let insertion = smt.add_with_proof(node(key), node(7)).unwrap();
+            let deletion = smt.delete_with_proof(node(key)).unwrap();
+
+            assert_eq!(
+                deletion,
+                TransitionProof {
+                    operation: SMTOperation::Delete,
+                    old_root: insertion.new_root.clone(),
+                    new_root: insertion.old_root.clone(),
+                    ..insertion
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_transition_details() {
+        let mut smt = SMT::new(hash_function, true);
+
+        let proof = smt.add_with_proof(node(1), node(10)).unwrap();
+        assert!(proof.is_old0);
+        assert!(proof.siblings.is_empty());
+        assert_eq!(proof.operation.fnc(), [1, 0]);
+
+        // 3 shares its first bit with 1, so the old leaf is found at the root.
+        let proof = smt.add_with_proof(node(3), node(30)).unwrap();
+        assert!(!proof.is_old0);
+        assert_eq!((proof.old_key, proof.old_value), (node(1), node(10)));
+        assert!(proof.siblings.is_empty());
+
+        let proof = smt.add_with_proof(node(2), node(20)).unwrap();
+        assert!(proof.is_old0);
+        assert_eq!(proof.siblings.len(), 1);
+
+        // After deleting 1, the leaf of 3 moves up next to the leaf of 2.
+        let proof = smt.delete_with_proof(node(1)).unwrap();
+        assert!(!proof.is_old0);
+        assert_eq!((proof.old_key, proof.old_value), (node(3), node(30)));
+        assert_eq!(proof.siblings.len(), 1);
+        assert_eq!(proof.operation.fnc(), [1, 1]);
+    }
+
+    #[test]
+    fn should_not_verify_wrong_transitions() {
+        let mut smt = SMT::new(hash_function, true);
+        let first_insertion = smt.add_with_proof(node(2), node(2)).unwrap();
+
+        for key in [5, 1, 13] {
+            smt.add(node(key), node(key)).unwrap();
+        }
+
+        let insertion = smt.add_with_proof(node(9), node(7)).unwrap();
+        let update = smt.update_with_proof(node(9), node(8)).unwrap();
+
+        let wrong_proofs = [
+            TransitionProof {
+                new_value: node(6),
+                ..insertion.clone()
+            },
+            TransitionProof {
+                new_root: insertion.old_root.clone(),
+                ..insertion.clone()
+            },
+            TransitionProof {
+                operation: SMTOperation::Delete,
+                ..insertion.clone()
+            },
+            TransitionProof {
+                is_old0: true,
+                ..insertion.clone()
+            },
+            TransitionProof {
+                old_key: node(3),
+                ..insertion.clone()
+            },
+            TransitionProof {
+                old_key: node(1),
+                ..update.clone()
+            },
+            TransitionProof {
+                old_key: node(3),
+                ..first_insertion.clone()
+            },
+            TransitionProof {
+                old_value: node(3),
+                ..first_insertion.clone()
+            },
+            TransitionProof {
+                siblings: vec![node(0); 300],
+                ..update.clone()
+            },
+        ];
+
+        for proof in &wrong_proofs {
+            assert!(!verify(proof));
+        }
+
+        assert!(first_insertion.is_old0);
+        assert!(verify(&first_insertion));
+        assert!(verify(&insertion));
+        assert!(verify(&update));
+        assert_eq!(
+            smt.add_with_proof(node(9), node(1)),
+            Err(SMTError::KeyAlreadyExist("9".to_string()))
+        );
+        assert_eq!(
+            smt.delete_with_proof(node(4)),
+            Err(SMTError::KeyDoesNotExist("4".to_string()))
+        );
+    }
+
+    #[test]
+    fn should_not_verify_transition_beyond_depth() {
+        let hash: HashFunction = hash_function;
+        let mut smt = SMT::with_depth(hash_function as HashFunction, 8).unwrap();
+
+        smt.add(node(1), node(1)).unwrap();
+        let insertion = smt.add_with_proof(node(129), node(7)).unwrap();
+        let update = smt.update_with_proof(node(129), node(8)).unwrap();
+
+        // 1 and 129 only diverge at their eighth bit.
+        assert_eq!(update.siblings.len(), 8);
+        assert_ne!(update.siblings.last(), Some(&node(0)));
+
+        for proof in [&insertion, &update] {
+            assert!(SMT::verify_transition(&hash, &node(0), &node(1), 8, proof));
+            assert!(!SMT::verify_transition(&hash, &node(0), &node(1), 7, proof));
+        }
+    }
+
+    #[test]
+    fn test_poseidon_transitions() {
+        let hasher = PoseidonSmtHasher::new();
+        let zero = Fr::from(0u64);
+        let one = Fr::from(1u64);
+        let mut smt = PoseidonSMT::poseidon();
+
+        for key in [7u64, 3, 12, 40, 41] {
+            let proof = smt
+                .add_with_proof(Fr::from(key), Fr::from(key * 2))
+                .unwrap();
+            assert!(SMT::verify_transition(&hasher, &zero, &one, 254, &proof));
+        }
+
+        let proof = smt.delete_with_proof(Fr::from(40u64)).unwrap();
+        assert!(SMT::verify_transition(&hasher, &zero, &one, 254, &proof));
+    }
+}
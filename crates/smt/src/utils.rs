@@ -1,66 +1,50 @@
-/// Converts a hexadecimal string to a binary string.
+/// Converts a key to a path of `depth` bits.
 ///
-/// # Arguments
-///
-/// * `n` - The hexadecimal string to convert.
-///
-/// # Returns
-///
-/// The binary representation of the hexadecimal string.
-pub fn hex_to_bin(n: &str) -> String {
-    let mut chars = n.chars();
-    let first_char = chars.next().unwrap();
-    let mut bin = format!(
-        "{:b}",
-        u8::from_str_radix(&first_char.to_string(), 16).unwrap()
-    );
-
-    for c in chars {
-        bin += &format!("{:04b}", u8::from_str_radix(&c.to_string(), 16).unwrap());
-    }
-
-    bin
-}
-
-/// Converts a hexadecimal key to a path represented as a vector of usize.
-///
-/// For each key, it is possible to obtain an array of 256 padded bits.
+/// The key is read as a big-endian unsigned integer and the path starts from its least
+/// significant bit, so leading zeros do not change the path.
 ///
 /// # Arguments
 ///
-/// * `key` - The hexadecimal key to convert.
+/// * `key` - The big-endian bytes of the key.
+/// * `depth` - The number of bits of the path.
 ///
 /// # Returns
 ///
-/// The path represented as a vector of usize.
-pub fn key_to_path(key: &str) -> Vec<usize> {
-    let bits = if let Ok(num) = u128::from_str_radix(key, 16) {
-        format!("{:b}", num)
-    } else {
-        hex_to_bin(key)
-    };
-
-    let padded_bits = format!("{:0>256}", bits).chars().rev().collect::<String>();
-    let bits_array = padded_bits
-        .chars()
-        .map(|c| c.to_digit(10).unwrap() as usize)
+/// The path represented as a vector of usize, or `None` if the key does not fit in `depth` bits.
+pub fn key_to_path(key: &[u8], depth: usize) -> Option<Vec<usize>> {
+    let bits = key.len() * 8;
+    let mut path: Vec<usize> = (0..bits.max(depth))
+        .map(|i| {
+            if i < bits {
+                (key[key.len() - 1 - i / 8] >> (i % 8) & 1) as usize
+            } else {
+                0
+            }
+        })
         .collect();
 
-    bits_array
+    if path[depth..].contains(&1) {
+        return None;
+    }
+
+    path.truncate(depth);
+
+    Some(path)
 }
 
 /// Returns the index of the last non-zero element in the array.
 ///
 /// # Arguments
 ///
-/// * `array` - The array of hexadecimal strings.
+/// * `array` - The array of nodes.
+/// * `zero` - The zero node.
 ///
 /// # Returns
 ///
 /// The index of the last non-zero element in the array, or -1 if no non-zero element is found.
-pub fn get_index_of_last_non_zero_element(array: Vec<&str>) -> isize {
-    for (i, &item) in array.iter().enumerate().rev() {
-        if u128::from_str_radix(item, 16).unwrap_or(0) != 0 {
+pub fn get_index_of_last_non_zero_element<T: PartialEq>(array: &[T], zero: &T) -> isize {
+    for (i, item) in array.iter().enumerate().rev() {
+        if item != zero {
             return i as isize;
         }
     }
@@ -107,47 +91,67 @@ pub fn is_hexadecimal(s: &str) -> bool {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_hex_to_bin() {
-        assert_eq!(hex_to_bin("A"), "1010");
-        assert_eq!(hex_to_bin("F"), "1111");
-        assert_eq!(hex_to_bin("1A"), "11010");
-        assert_eq!(hex_to_bin("FF"), "11111111");
-        assert_eq!(hex_to_bin("12"), "10010");
-    }
-
     #[test]
     fn test_key_to_path() {
-        let path = key_to_path("17");
+        let path = key_to_path(&[0x17], 256).unwrap();
         assert_eq!(path.len(), 256);
         assert_eq!(&path[0..5], vec![1, 1, 1, 0, 1]);
+        assert!(path[5..].iter().all(|&bit| bit == 0));
+
+        // Leading zeros do not change the path.
+        assert_eq!(
+            key_to_path(&[0, 0, 0x01, 0x02], 64),
+            key_to_path(&[0x01, 0x02], 64)
+        );
+        assert_eq!(
+            &key_to_path(&[0x01, 0x02], 16).unwrap()[0..10],
+            vec![0, 1, 0, 0, 0, 0, 0, 0, 1, 0]
+        );
+
+        assert_eq!(key_to_path(&[0xff], 8), Some(vec![1; 8]));
+        assert_eq!(key_to_path(&[0xff], 7), None);
+        assert_eq!(key_to_path(&[0x7f], 7), Some(vec![1; 7]));
+        assert_eq!(key_to_path(&[], 4), Some(vec![0; 4]));
     }
 
     #[test]
     fn test_get_index_of_last_non_zero_element() {
-        assert_eq!(get_index_of_last_non_zero_element(vec![]), -1);
-        assert_eq!(get_index_of_last_non_zero_element(vec!["0", "0", "0"]), -1);
+        assert_eq!(get_index_of_last_non_zero_element(&[], &"0"), -1);
+        assert_eq!(
+            get_index_of_last_non_zero_element(&["0", "0", "0"], &"0"),
+            -1
+        );
 
-        assert_eq!(get_index_of_last_non_zero_element(vec!["0", "0", "1"]), 2);
-        assert_eq!(get_index_of_last_non_zero_element(vec!["0", "1", "0"]), 1);
-        assert_eq!(get_index_of_last_non_zero_element(vec!["1", "0", "0"]), 0);
+        assert_eq!(
+            get_index_of_last_non_zero_element(&["0", "0", "1"], &"0"),
+            2
+        );
+        assert_eq!(
+            get_index_of_last_non_zero_element(&["0", "1", "0"], &"0"),
+            1
+        );
+        assert_eq!(
+            get_index_of_last_non_zero_element(&["1", "0", "0"], &"0"),
+            0
+        );
 
         assert_eq!(
-            get_index_of_last_non_zero_element(vec!["0", "1", "0", "1", "0"]),
+            get_index_of_last_non_zero_element(&["0", "1", "0", "1", "0"], &"0"),
             3
         );
         assert_eq!(
-            get_index_of_last_non_zero_element(vec!["1", "0", "1", "0", "0"]),
+            get_index_of_last_non_zero_element(&["1", "0", "1", "0", "0"], &"0"),
             2
         );
         assert_eq!(
-            get_index_of_last_non_zero_element(vec!["0", "0", "0", "1", "1"]),
+            get_index_of_last_non_zero_element(&["0", "0", "0", "1", "1"], &"0"),
             4
         );
         assert_eq!(
-            get_index_of_last_non_zero_element(vec![
-                "0", "17", "3", "0", "3", "0", "3", "2", "0", "0"
-            ]),
+            get_index_of_last_non_zero_element(
+                &["0", "17", "3", "0", "3", "0", "3", "2", "0", "0"],
+                &"0"
+            ),
             7
         )
     }